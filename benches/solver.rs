@@ -0,0 +1,149 @@
+// Benchmarks of the hot paths of the search, on fixed deals so runs can be compared.
+//
+// Save a baseline before a change and compare against it after:
+//   cargo bench --bench solver -- --save-baseline before
+//   cargo bench --bench solver -- --baseline before
+// Criterion keeps baselines under target/criterion, which isn't committed. The numbers of the
+// last reference run, and the machine they were measured on, are in benches/baseline.md.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use solitaire_solvability::{
+    deal::Deal,
+    moves::Move,
+    results::Outcome,
+    rules::Rules,
+    solver::{Budget, SearchConfig, Solver},
+    Game,
+};
+use std::time::Duration;
+
+const SEEDS: [u32; 8] = [0, 1, 2, 3, 4, 5, 6, 7];
+
+// Positions met along a fixed walk through each deal, a mix of early and late game
+fn positions() -> Vec<Game> {
+    let mut positions = Vec::new();
+    for seed in SEEDS {
+        let mut game = Game::from_deal(&Deal::from_seed(seed), Rules::default());
+        for step in 0..200 {
+            let mut moves = game.valid_moves().into_iter().collect::<Vec<_>>();
+            if moves.is_empty() {
+                break;
+            }
+            moves.sort();
+            positions.push(game.clone());
+            game = game.handle_move(&moves[step % moves.len()]).unwrap();
+        }
+    }
+    positions
+}
+
+// Deals of the regression corpus that take at most 500 states to solve
+const EASY_SEEDS: [u32; 83] = [
+    1055199751, 1509008567, 782792687, 3919743386, 1618925159, 345125463, 3719554129, 1375140597,
+    392867212, 2706445464, 4169203838, 1151773354, 936956175, 2987535806, 726540894, 2682348534,
+    17293944, 3418005422, 1954906809, 399234176, 3253702467, 3590182231, 2354019288, 1238842687,
+    2304778026, 3579812150, 580890263, 2502868284, 2506158943, 213274975, 2912804623, 3152037901,
+    2585105627, 2764886885, 127227545, 2772374722, 2711881804, 2978991261, 750591289, 2356565671,
+    702550526, 4247632364, 2115111846, 864338322, 3134910812, 337279306, 1144377303, 2196459527,
+    1536619269, 419488657, 2402366345, 837468859, 1344917242, 406603210, 4281914311, 161665267,
+    2074867532, 251099359, 1560925302, 615815774, 1812573736, 101604137, 3855015509, 3700860288,
+    3479310421, 2300847019, 2210462219, 3912153487, 3796829608, 2591367361, 789083196, 988789054,
+    2544396247, 3363049643, 3509830787, 1272262997, 1447482542, 950057869, 2527907136, 3012436031,
+    2370711373, 975105533, 249074061,
+];
+
+// Deals of the regression corpus that take between 10k and 250k states to solve
+const HARD_SEEDS: [u32; 11] = [
+    681733007, 4035168353, 2385713334, 2315339886, 3035812914, 1759113897, 490447741, 2478293856,
+    965792215, 956027937, 1497363408,
+];
+
+// Deals of the regression corpus that take more than 200k states to solve
+const UNRESOLVED_SEEDS: [u32; 10] = [
+    1268242272, 3654388914, 919206715, 3282031814, 3367802734, 3876665739, 1989802602, 2955279615,
+    2453146303, 3797994024,
+];
+
+fn solve(seed: u32, budget: Budget) -> Outcome {
+    let config =
+        SearchConfig::from_deal(Deal::from_seed(seed), Rules::default()).with_budget(budget);
+    Solver::new(config).solve().outcome
+}
+
+fn move_generation(c: &mut Criterion) {
+    let positions = positions();
+    let mut group = c.benchmark_group("positions");
+    group.throughput(Throughput::Elements(positions.len() as u64));
+    group.bench_function("valid_moves", |b| {
+        b.iter(|| {
+            positions
+                .iter()
+                .map(|game| game.valid_moves().len())
+                .sum::<usize>()
+        })
+    });
+    group.bench_function("compact_state", |b| {
+        b.iter(|| {
+            positions
+                .iter()
+                .map(Game::compact_state)
+                .collect::<Vec<_>>()
+        })
+    });
+    let moves = positions
+        .iter()
+        .flat_map(|game| game.valid_moves().into_iter().map(move |mv| (game, mv)))
+        .collect::<Vec<(&Game, Move)>>();
+    group.throughput(Throughput::Elements(moves.len() as u64));
+    group.bench_function("handle_move", |b| {
+        b.iter(|| {
+            moves
+                .iter()
+                .map(|(game, mv)| game.handle_move(mv).unwrap())
+                .collect::<Vec<_>>()
+        })
+    });
+    group.finish();
+}
+
+fn solver_nodes(c: &mut Criterion) {
+    // Deals that don't resolve within this many states, so every run expands exactly as many
+    const MAX_NODES: u64 = 20_000;
+    let budget = Budget::nodes(MAX_NODES);
+    let seeds = UNRESOLVED_SEEDS;
+    let mut group = c.benchmark_group("solver");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(MAX_NODES * seeds.len() as u64));
+    group.bench_function("nodes", |b| {
+        b.iter(|| {
+            for seed in &seeds {
+                assert_eq!(solve(*seed, budget), Outcome::Timeout);
+            }
+        })
+    });
+    group.finish();
+}
+
+fn reference_set(c: &mut Criterion) {
+    let budget = Budget::unlimited();
+    let mut group = c.benchmark_group("reference_set");
+    group.sample_size(10);
+    let easy = EASY_SEEDS;
+    group.bench_function(BenchmarkId::new("easy", easy.len()), |b| {
+        b.iter(|| {
+            for seed in &easy {
+                assert_eq!(solve(*seed, budget), Outcome::Solved);
+            }
+        })
+    });
+    group.measurement_time(Duration::from_secs(20));
+    for seed in HARD_SEEDS {
+        group.bench_function(BenchmarkId::new("hard", seed), |b| {
+            b.iter(|| assert_eq!(solve(seed, budget), Outcome::Solved))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, move_generation, solver_nodes, reference_set);
+criterion_main!(benches);
@@ -0,0 +1,319 @@
+use serde::Deserialize;
+
+use crate::{
+    results::{Outcome, SolveResult},
+    solver::Budget,
+};
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::{self, File},
+    io::{self, BufRead, BufReader},
+    ops::Range,
+    path::Path,
+    str::FromStr,
+};
+
+// A contiguous range of deal seeds, written `start..end` with `end` excluded
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SeedRange(pub Range<u32>);
+
+impl FromStr for SeedRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once("..")
+            .ok_or_else(|| format!("`{}` is not a seed range like `0..1000`", s))?;
+        let parse = |bound: &str| {
+            bound
+                .parse::<u32>()
+                .map_err(|err| format!("invalid seed `{}`: {}", bound, err))
+        };
+        Ok(SeedRange(parse(start)?..parse(end)?))
+    }
+}
+
+impl fmt::Display for SeedRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}..{}", self.0.start, self.0.end)
+    }
+}
+
+// One of `count` disjoint slices of a seed range, written `index/count`. Seeds are
+// interleaved between shards so hard deals don't cluster on a single process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shard {
+    pub index: u32,
+    pub count: u32,
+}
+
+impl Shard {
+    pub fn contains(&self, seed: u32) -> bool {
+        seed % self.count == self.index
+    }
+}
+
+impl Default for Shard {
+    fn default() -> Self {
+        Self { index: 0, count: 1 }
+    }
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("`{}` is not a shard like `0/4`", s);
+        let (index, count) = s.split_once('/').ok_or_else(err)?;
+        let index = index.parse::<u32>().map_err(|_| err())?;
+        let count = count.parse::<u32>().map_err(|_| err())?;
+        if index >= count {
+            return Err(format!(
+                "shard index {} must be below the shard count {}",
+                index, count
+            ));
+        }
+        Ok(Self { index, count })
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+// Seed and outcome of the deals already recorded in a results file. Only those two fields
+// of each record are read, and lines that don't parse (like one cut short by a crash) are
+// skipped, so that a batch can always be resumed from whatever the previous run managed
+// to write.
+pub fn completed_deals(path: impl AsRef<Path>) -> io::Result<HashMap<u32, Outcome>> {
+    #[derive(Deserialize)]
+    struct SeedOnly {
+        seed: u32,
+    }
+    #[derive(Deserialize)]
+    struct Record {
+        deal: SeedOnly,
+        outcome: Outcome,
+    }
+
+    // Read a line at a time, as the results of a large batch run to gigabytes
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(err) => return Err(err),
+    };
+    let mut completed = HashMap::new();
+    for line in BufReader::new(file).lines() {
+        if let Ok(record) = serde_json::from_str::<Record>(&line?) {
+            completed.insert(record.deal.seed, record.outcome);
+        }
+    }
+    Ok(completed)
+}
+
+// Keeps only the deals a search settled, `Solved` or `Unsolvable`. A retry list holds the deals
+// that timed out, which are recorded in the results of the batch it came from, so a batch of
+// the list against those results must search them again rather than skip them.
+pub fn retain_resolved(completed: &mut HashMap<u32, Outcome>) {
+    completed.retain(|_, outcome| matches!(outcome, Outcome::Solved | Outcome::Unsolvable));
+}
+
+// Reads a list of seeds written one per line, like the retry list of a batch
+pub fn read_seed_list(path: impl AsRef<Path>) -> io::Result<Vec<u32>> {
+    fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.parse::<u32>().map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid seed `{}`: {}", line, err),
+                )
+            })
+        })
+        .collect()
+}
+
+// Seeds that belong to `shard` and are not in `completed`, and the number of the shard's
+// seeds that are
+pub fn pending_seeds(
+    seeds: impl IntoIterator<Item = u32>,
+    shard: Shard,
+    completed: &HashMap<u32, Outcome>,
+) -> (Vec<u32>, usize) {
+    let mut recorded = 0;
+    let pending = seeds
+        .into_iter()
+        .filter(|seed| shard.contains(*seed))
+        .filter(|seed| {
+            let is_recorded = completed.contains_key(seed);
+            recorded += is_recorded as usize;
+            !is_recorded
+        })
+        .collect();
+    (pending, recorded)
+}
+
+// Solves a deal with `initial` budget, growing it geometrically by `factor` and starting
+// over each time the search times out, until the deal resolves or `cap` has been tried. An
+// `initial` budget past `cap` is tried once as it is.
+// `solve` runs one attempt with the budget it is given. The returned result carries the
+// budget of the last attempt, which is the budget the deal resolved at unless its outcome
+// is still `Timeout`.
+pub fn solve_with_escalation(
+    initial: Budget,
+    factor: f64,
+    cap: Budget,
+    mut solve: impl FnMut(Budget) -> SolveResult,
+) -> SolveResult {
+    let mut budget = initial;
+    loop {
+        let result = solve(budget);
+        let next_budget = budget.scaled(factor, &cap);
+        if result.outcome != Outcome::Timeout || next_budget == budget {
+            return result;
+        }
+        budget = next_budget;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        deal::Deal,
+        rules::Rules,
+        solver::{SearchConfig, Solver},
+    };
+
+    // The budgets `solve_with_escalation` tries on a deal that times out until `resolves_at`
+    fn attempts(initial: Budget, cap: Budget, resolves_at: u64) -> (Vec<Budget>, SolveResult) {
+        let mut budgets = Vec::new();
+        let result = solve_with_escalation(initial, 4.0, cap, |budget| {
+            budgets.push(budget);
+            let outcome = if budget.max_nodes.unwrap() >= resolves_at {
+                Outcome::Solved
+            } else {
+                Outcome::Timeout
+            };
+            SolveResult {
+                outcome,
+                ..Solver::new(
+                    SearchConfig::from_deal(Deal::from_seed(0), Rules::default())
+                        .with_budget(budget),
+                )
+                .solve()
+            }
+        });
+        (budgets, result)
+    }
+
+    #[test]
+    fn escalation_grows_the_budget_until_the_deal_resolves() {
+        let (budgets, result) = attempts(Budget::nodes(10), Budget::nodes(10_000), 100);
+        assert_eq!(
+            budgets,
+            [Budget::nodes(10), Budget::nodes(40), Budget::nodes(160)]
+        );
+        assert_eq!(result.outcome, Outcome::Solved);
+        assert_eq!(result.budget, Budget::nodes(160));
+    }
+
+    #[test]
+    fn escalation_stops_at_the_cap() {
+        let (budgets, result) = attempts(Budget::nodes(10), Budget::nodes(100), u64::MAX);
+        assert_eq!(
+            budgets,
+            [Budget::nodes(10), Budget::nodes(40), Budget::nodes(100)]
+        );
+        assert_eq!(result.outcome, Outcome::Timeout);
+    }
+
+    #[test]
+    fn escalation_past_the_cap_tries_once() {
+        let (budgets, result) = attempts(Budget::nodes(1000), Budget::nodes(100), u64::MAX);
+        assert_eq!(budgets, [Budget::nodes(1000)]);
+        assert_eq!(result.outcome, Outcome::Timeout);
+    }
+
+    #[test]
+    fn listed_timeouts_are_searched_again() {
+        let path = std::env::temp_dir().join(format!("completed-{}.jsonl", std::process::id()));
+        let records = [
+            (1, "solved"),
+            (2, "timeout"),
+            (3, "unsolvable"),
+            (4, "error"),
+        ]
+        .map(|(seed, outcome)| {
+            format!(r#"{{"deal":{{"seed":{}}},"outcome":"{}"}}"#, seed, outcome)
+        });
+        fs::write(&path, records.join("\n")).unwrap();
+        let mut completed = completed_deals(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            pending_seeds(1..5, Shard::default(), &completed),
+            (vec![], 4)
+        );
+        retain_resolved(&mut completed);
+        assert_eq!(
+            pending_seeds(1..5, Shard::default(), &completed),
+            (vec![2, 4], 2)
+        );
+    }
+
+    #[test]
+    fn seed_ranges_parse() {
+        assert_eq!("0..10".parse(), Ok(SeedRange(0..10)));
+        assert_eq!("10..10".parse(), Ok(SeedRange(10..10)));
+        let range = "5..4294967295".parse::<SeedRange>().unwrap();
+        assert_eq!(range.to_string(), "5..4294967295");
+        for invalid in ["", "10", "0-10", "a..10", "0..", "-1..10", "0..4294967296"] {
+            assert!(invalid.parse::<SeedRange>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn shards_parse() {
+        assert_eq!("1/4".parse(), Ok(Shard { index: 1, count: 4 }));
+        assert_eq!(Shard::default().to_string(), "0/1");
+        for invalid in ["", "1", "1/", "/4", "a/4", "4/4", "5/4", "0/0"] {
+            assert!(invalid.parse::<Shard>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn shards_split_the_seeds() {
+        let shards = (0..3).map(|index| Shard { index, count: 3 });
+        for seed in 0..30 {
+            let containing = shards.clone().filter(|shard| shard.contains(seed)).count();
+            assert_eq!(containing, 1);
+        }
+    }
+
+    #[test]
+    fn pending_seeds_skip_other_shards_and_recorded_deals() {
+        let completed = HashMap::from([
+            (1, Outcome::Solved),
+            (4, Outcome::Unsolvable),
+            (6, Outcome::Timeout),
+            (7, Outcome::Solved),
+        ]);
+        let shard = Shard { index: 0, count: 2 };
+        // 1 and 7 are recorded, but in the other shard
+        assert_eq!(pending_seeds(0..10, shard, &completed), (vec![0, 2, 8], 2));
+        assert_eq!(
+            pending_seeds([7, 3, 4], Shard::default(), &completed),
+            (vec![3], 2)
+        );
+        assert_eq!(
+            pending_seeds(0..5, shard, &HashMap::new()),
+            (vec![0, 2, 4], 0)
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use siphasher::sip128::{Hasher128, SipHasher13};
+use std::hash::Hasher;
+
+// Bits per state the number of hash functions is tuned for. At that load about 1% of new
+// states are mistaken for visited ones, and the rate climbs as the filter fills up.
+const BITS_PER_ITEM: u64 = 10;
+
+// A set that can only tell for sure that an item was never inserted. `contains` may answer
+// yes for an item that wasn't, in exchange for a fixed and much smaller memory footprint
+// than a `HashSet`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    hashes: u32,
+    len: usize,
+}
+
+impl BloomFilter {
+    // A filter taking up about `memory_bytes`
+    pub fn with_memory(memory_bytes: usize) -> Self {
+        let words = (memory_bytes / 8).max(1);
+        // The optimal number of hashes for the intended load, ln 2 times the bits per item
+        let hashes = (BITS_PER_ITEM as f64 * std::f64::consts::LN_2).round() as u32;
+        Self {
+            bits: vec![0; words],
+            hashes,
+            len: 0,
+        }
+    }
+
+    // Two hashes of an item, combined into the positions of its bits as in Kirsch and
+    // Mitzenmacher's "Less hashing, same performance". The bits are saved in checkpoints, so
+    // the hash must never change: the halves of the 128-bit SipHash-1-3 of the bytes, with
+    // keys of zero, rather than std's `DefaultHasher` whose algorithm isn't specified.
+    fn hash_pair(item: &[u8]) -> (u64, u64) {
+        let mut hasher = SipHasher13::new();
+        hasher.write(item);
+        let hash = hasher.finish128();
+        // Keep the step odd so it never cycles over a few positions
+        (hash.h1, hash.h2 | 1)
+    }
+
+    fn bit_index(&self, (first, step): (u64, u64), idx: u32) -> usize {
+        let num_bits = self.bits.len() as u64 * 64;
+        (first.wrapping_add((idx as u64).wrapping_mul(step)) % num_bits) as usize
+    }
+
+    pub fn insert(&mut self, item: &[u8]) {
+        let hash_pair = Self::hash_pair(item);
+        for idx in 0..self.hashes {
+            let bit = self.bit_index(hash_pair, idx);
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+        self.len += 1;
+    }
+
+    pub fn contains(&self, item: &[u8]) -> bool {
+        let hash_pair = Self::hash_pair(item);
+        (0..self.hashes).all(|idx| {
+            let bit = self.bit_index(hash_pair, idx);
+            self.bits[bit / 64] & (1 << (bit % 64)) != 0
+        })
+    }
+
+    // Number of insertions, counting an item inserted twice twice
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.bits.fill(0);
+        self.len = 0;
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.bits.len() * 8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inserted_items_are_always_found() {
+        let mut filter = BloomFilter::with_memory(1024);
+        for item in 0..5_000u32 {
+            filter.insert(&item.to_le_bytes());
+        }
+        assert!((0..5_000u32).all(|item| filter.contains(&item.to_le_bytes())));
+        assert_eq!(filter.len(), 5_000);
+    }
+
+    #[test]
+    fn false_positive_rate_is_low_at_the_intended_load() {
+        let memory_bytes = 8 * 1024;
+        let load = memory_bytes as u64 * 8 / BITS_PER_ITEM;
+        let mut filter = BloomFilter::with_memory(memory_bytes);
+        for item in 0..load {
+            filter.insert(&item.to_le_bytes());
+        }
+        let false_positives = (load..2 * load)
+            .filter(|item| filter.contains(&item.to_le_bytes()))
+            .count();
+        // About 1% expected, with some slack for the hash
+        assert!(
+            false_positives * 100 < 3 * load as usize,
+            "{} false positives out of {}",
+            false_positives,
+            load
+        );
+    }
+
+    #[test]
+    fn hash_never_changes() {
+        // Checkpoints saved by any build must find the same bits
+        assert_eq!(
+            BloomFilter::hash_pair(b""),
+            (2392699331323474957, 13338640444649321219)
+        );
+        assert_eq!(
+            BloomFilter::hash_pair(b"klondike"),
+            (6484006688372475702, 315279486008376261)
+        );
+    }
+}
@@ -5,14 +5,6 @@ pub const NUM_CARDS_SUIT: u8 = 13;
 
 pub type Card = u8;
 
-fn abs_diff(a: u8, b: u8) -> u8 {
-    if a > b {
-        a - b
-    } else {
-        b - a
-    }
-}
-
 pub trait IsCard {
     fn index(&self) -> u8;
     fn is_face_up(&self) -> bool;
@@ -102,7 +94,7 @@ pub fn are_card_colors_different(card1: Card, card2: Card) -> bool {
 pub fn are_card_suits_the_same(card1: Card, card2: Card) -> bool {
     let card_rank_1 = card_rank(card1);
     let card_rank_2 = card_rank(card2);
-    abs_diff(card_rank_1, card_rank_2) <= 12 && !are_card_colors_different(card1, card2)
+    card_rank_1.abs_diff(card_rank_2) <= 12 && !are_card_colors_different(card1, card2)
 }
 
 pub fn suit_rank(card: Card) -> u8 {
@@ -117,36 +109,41 @@ pub fn ranking_of_kings() -> usize {
     13 * 4
 }
 
+// Plain name of a card, like `HQ` for the queen of hearts
+pub fn card_name(card: Card) -> String {
+    let suit_rank = suit_rank(card);
+    let card_rank = card_rank(card);
+    let suit_string = match suit_rank {
+        0 => "C",
+        1 => "S",
+        2 => "H",
+        3 => "D",
+        _ => unreachable!(),
+    };
+    let rank_string = match card_rank {
+        1 => "A",
+        2 => "2",
+        3 => "3",
+        4 => "4",
+        5 => "5",
+        6 => "6",
+        7 => "7",
+        8 => "8",
+        9 => "9",
+        10 => "10",
+        11 => "J",
+        12 => "Q",
+        13 => "K",
+        _ => unreachable!(),
+    };
+    format!("{}{}", suit_string, rank_string)
+}
+
 pub fn pretty_string(card: Card) -> String {
     if card == u8::MAX {
         " ".to_string()
     } else {
-        let suit_rank = suit_rank(card);
-        let card_rank = card_rank(card);
-        let suit_string = match suit_rank {
-            0 => "C",
-            1 => "D",
-            2 => "S",
-            3 => "H",
-            _ => unreachable!(),
-        };
-        let rank_string = match card_rank {
-            1 => "A",
-            2 => "2",
-            3 => "3",
-            4 => "4",
-            5 => "5",
-            6 => "6",
-            7 => "7",
-            8 => "8",
-            9 => "9",
-            10 => "10",
-            11 => "J",
-            12 => "Q",
-            13 => "K",
-            _ => unreachable!(),
-        };
-        let ret_string = format!("{}{}", suit_string, rank_string);
+        let ret_string = card_name(card);
         let mut colored = if is_red(card) {
             ret_string.red()
         } else {
@@ -0,0 +1,185 @@
+use crate::{card::*, rules::NUM_TABLEAUS, Game};
+
+impl Game {
+    // The cards of the next rank that `card` can be moved onto: the two of the other colour,
+    // or all four when colours are relaxed
+    fn parents(&self, card: Card) -> impl Iterator<Item = Card> + '_ {
+        let rank = card_rank(card);
+        (0..NUM_CARDS_DECK / NUM_CARDS_SUIT)
+            .map(move |suit| suit * NUM_CARDS_SUIT + rank)
+            .filter(move |parent| {
+                rank < NUM_CARDS_SUIT && self.can_be_placed_on_top_of(*parent, card)
+            })
+    }
+
+    // Whether some cards can never leave their tableau, so that the game is lost whatever the
+    // moves. Such stuck cards are:
+    // - not kings, which could go to an empty tableau, nor any card if the rules allow it there
+    // - not sitting on a face-up parent they could be moved along with
+    // - unable to go to a foundation, a lower card of their suit being under a stuck card
+    // - unable to go onto another tableau, each of their parents being on a foundation or
+    //   under a stuck card
+    // Cards can block each other this way across tableaus, so the stuck ones are found by
+    // starting from every card meeting the first two conditions and dropping those that
+    // miss one of the others until none is left to drop.
+    pub fn is_deadlocked(&self) -> bool {
+        let mut stuck = [false; NUM_CARDS_DECK as usize];
+        // Tableau and position of every card in a tableau
+        let mut positions = [None; NUM_CARDS_DECK as usize];
+        for (tableau_idx, tableau) in self.tableaus.iter().enumerate() {
+            for (card_idx, card) in tableau.0.iter().enumerate() {
+                positions[card.index() as usize] = Some((tableau_idx, card_idx));
+                let on_parent = card_idx > 0 && {
+                    let below = tableau.0[card_idx - 1];
+                    card.is_face_up()
+                        && below.is_face_up()
+                        && self.can_be_placed_on_top_of(below, *card)
+                };
+                stuck[card.index() as usize] = !self.can_go_to_empty_tableau(*card) && !on_parent;
+            }
+        }
+
+        loop {
+            // Position of the highest stuck card of each tableau, everything under it is
+            // there for good
+            let mut highest_stuck = [None; NUM_TABLEAUS];
+            for (tableau_idx, tableau) in self.tableaus.iter().enumerate() {
+                highest_stuck[tableau_idx] = tableau
+                    .0
+                    .iter()
+                    .rposition(|card| stuck[card.index() as usize]);
+            }
+            let is_covered = |card: Card| {
+                positions[card as usize].is_some_and(|(tableau_idx, card_idx)| {
+                    highest_stuck[tableau_idx].is_some_and(|highest| highest > card_idx)
+                })
+            };
+            let on_foundation = |card: Card| self.foundation_stack & (1 << card) != 0;
+            let can_leave = |card: Card| {
+                let ace = suit_rank(card) * NUM_CARDS_SUIT;
+                let to_foundation = !(ace..card).any(is_covered);
+                let to_tableau = self
+                    .parents(card)
+                    .any(|parent| !on_foundation(parent) && !is_covered(parent));
+                to_foundation || to_tableau
+            };
+            let freed = (0..NUM_CARDS_DECK)
+                .filter(|card| stuck[*card as usize] && can_leave(*card))
+                .collect::<Vec<_>>();
+            if freed.is_empty() {
+                return stuck.contains(&true);
+            }
+            freed
+                .into_iter()
+                .for_each(|card| stuck[card as usize] = false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        deal::Deal,
+        rules::{Relaxation, Rules},
+    };
+
+    // Positions in the deck of the cards dealt to tableaus 5 and 6, from the bottom up
+    const TABLEAU_5: [usize; 6] = [46, 40, 35, 31, 28, 26];
+    const TABLEAU_6: [usize; 7] = [45, 39, 34, 30, 27, 25, 24];
+
+    // The deal of seed 0 with some cards swapped into the given positions of the deck
+    fn game_with(placed: &[(usize, Card)]) -> Game {
+        let mut deal = Deal::from_seed(0);
+        for (position, card) in placed {
+            let from = deal.cards.iter().position(|dealt| dealt == card).unwrap();
+            deal.cards.swap(*position, from);
+        }
+        Game::from_deal(&deal, Rules::default())
+    }
+
+    #[test]
+    fn parents_are_the_next_rank_of_the_other_colour() {
+        let game = game_with(&[]);
+        let parents = |card| game.parents(card).collect::<Vec<_>>();
+        // The 6 of clubs goes on the 7 of hearts or of diamonds
+        assert_eq!(parents(CLUBS_ACE + 5), [HEARTS_ACE + 6, DIAMONDS_ACE + 6]);
+        // The queen of hearts on the king of clubs or of spades
+        assert_eq!(parents(HEARTS_ACE + 11), [CLUBS_KING, SPADES_KING]);
+        // Kings go on no card
+        assert!(parents(SPADES_KING).is_empty());
+    }
+
+    #[test]
+    fn relaxed_colors_give_parents_of_every_suit() {
+        let mut game = game_with(&[]);
+        game.rules.relaxation = Relaxation {
+            ignore_colors: true,
+            ..Relaxation::default()
+        };
+        assert_eq!(game.parents(CLUBS_ACE + 5).count(), 4);
+    }
+
+    #[test]
+    fn relaxed_empty_tableaus_leave_no_card_stuck() {
+        let mut game = game_with(&[
+            (TABLEAU_6[0], CLUBS_ACE + 1),
+            (TABLEAU_6[1], HEARTS_ACE + 5),
+            (TABLEAU_6[2], DIAMONDS_ACE + 5),
+            (TABLEAU_6[3], CLUBS_ACE + 4),
+        ]);
+        assert!(game.is_deadlocked());
+        game.rules.relaxation = Relaxation {
+            any_card_on_empty_tableau: true,
+            ..Relaxation::default()
+        };
+        assert!(!game.is_deadlocked());
+    }
+
+    #[test]
+    fn solvable_deal_is_not_deadlocked() {
+        assert!(!game_with(&[]).is_deadlocked());
+    }
+
+    #[test]
+    fn card_over_a_lower_card_of_its_suit_and_its_parents_is_stuck() {
+        // The 5 of clubs can only leave onto a red 6, and both are under it with the 2 of
+        // clubs it has to wait for
+        let game = game_with(&[
+            (TABLEAU_6[0], CLUBS_ACE + 1),
+            (TABLEAU_6[1], HEARTS_ACE + 5),
+            (TABLEAU_6[2], DIAMONDS_ACE + 5),
+            (TABLEAU_6[3], CLUBS_ACE + 4),
+        ]);
+        assert!(game.is_deadlocked());
+
+        // With a red 6 elsewhere, the 5 of clubs can make way for the 2
+        let game = game_with(&[
+            (TABLEAU_6[0], CLUBS_ACE + 1),
+            (TABLEAU_6[1], HEARTS_ACE + 5),
+            (TABLEAU_6[3], CLUBS_ACE + 4),
+        ]);
+        assert!(!game.is_deadlocked());
+    }
+
+    #[test]
+    fn cards_blocking_each_other_across_tableaus_are_stuck() {
+        // The 5 of clubs waits for a red 6 under the 5 of hearts, which waits for a black 6
+        // under the 5 of clubs
+        let mut placed = vec![
+            (TABLEAU_6[0], CLUBS_ACE + 1),
+            (TABLEAU_6[1], CLUBS_ACE + 5),
+            (TABLEAU_6[2], SPADES_ACE + 5),
+            (TABLEAU_6[3], CLUBS_ACE + 4),
+            (TABLEAU_5[0], HEARTS_ACE + 1),
+            (TABLEAU_5[1], HEARTS_ACE + 5),
+            (TABLEAU_5[2], DIAMONDS_ACE + 5),
+            (TABLEAU_5[3], HEARTS_ACE + 4),
+        ];
+        assert!(game_with(&placed).is_deadlocked());
+
+        // Freeing one red 6 breaks the cycle
+        placed.retain(|(_, card)| *card != DIAMONDS_ACE + 5);
+        assert!(!game_with(&placed).is_deadlocked());
+    }
+}
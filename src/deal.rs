@@ -0,0 +1,35 @@
+use arrayvec::ArrayVec;
+use mersenne_twister::MT19937;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::card::*;
+
+// The shuffled deck a game is dealt from. The last card is dealt first, see `Game::initial_deal`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deal {
+    pub seed: u32,
+    pub cards: ArrayVec<Card, 52>,
+}
+
+impl Deal {
+    pub fn from_seed(seed: u32) -> Self {
+        let mut random_engine = MT19937::default();
+        random_engine.reseed(seed);
+        let mut cards = (0..NUM_CARDS_DECK).collect::<ArrayVec<Card, 52>>();
+        random_engine.shuffle(&mut cards);
+        Self { seed, cards }
+    }
+
+    // Whether every card of the deck appears exactly once
+    pub fn is_full_deck(&self) -> bool {
+        let mut seen: u64 = 0;
+        for card in &self.cards {
+            if *card >= NUM_CARDS_DECK || seen & (1 << card) != 0 {
+                return false;
+            }
+            seen |= 1 << card;
+        }
+        self.cards.len() == NUM_CARDS_DECK as usize
+    }
+}
@@ -0,0 +1,434 @@
+use log::{debug, error};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    marker::PhantomData,
+    mem,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    moves::Move,
+    results::{Deduplication, Outcome, SolveResult},
+    solver::{GameCompact, SearchConfig, SearchStats, Solver},
+    strategy::Strategy,
+    Game,
+};
+
+// Bytes of successors the external search collects in memory before spilling them to disk
+pub const DEFAULT_MEMORY_LIMIT: usize = 512 << 20;
+
+// Number of successors that fit in `memory_bytes`, at least one
+fn successors_in(memory_bytes: usize) -> usize {
+    (memory_bytes / mem::size_of::<(GameCompact, Game)>()).max(1)
+}
+
+// Numbers the scratch directories of the searches of this process, so that two searches of
+// the same deal never share one
+static NEXT_SEARCH_ID: AtomicUsize = AtomicUsize::new(0);
+
+// A file of bincode records of one type, written and read back in order
+struct RecordWriter<T> {
+    writer: BufWriter<File>,
+    len: usize,
+    record: PhantomData<T>,
+}
+
+impl<T: Serialize> RecordWriter<T> {
+    fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+            len: 0,
+            record: PhantomData,
+        })
+    }
+
+    fn write(&mut self, record: &T) -> io::Result<()> {
+        self.len += 1;
+        bincode::serialize_into(&mut self.writer, record).map_err(|err| into_io_error(*err))
+    }
+
+    // Flushes the file, returning the number of records written
+    fn finish(mut self) -> io::Result<usize> {
+        self.writer.flush()?;
+        Ok(self.len)
+    }
+}
+
+struct RecordReader<T> {
+    reader: BufReader<File>,
+    record: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> RecordReader<T> {
+    fn open(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            reader: BufReader::new(File::open(path)?),
+            record: PhantomData,
+        })
+    }
+
+    fn next(&mut self) -> io::Result<Option<T>> {
+        match bincode::deserialize_from(&mut self.reader) {
+            Ok(record) => Ok(Some(record)),
+            Err(err) => match *err {
+                bincode::ErrorKind::Io(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    Ok(None)
+                }
+                err => Err(into_io_error(err)),
+            },
+        }
+    }
+}
+
+fn into_io_error(err: bincode::ErrorKind) -> io::Error {
+    match err {
+        bincode::ErrorKind::Io(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidData, err),
+    }
+}
+
+// Why the search stopped before running out of states
+enum Stop {
+    Solved(Vec<Move>),
+    Budget(Outcome),
+}
+
+// Searches a deal breadth-first with the visited set and the frontier on disk, so the number
+// of states it can go through is bounded by disk space rather than memory. Meant for proving
+// deals unsolvable: finding a solution takes longer than with `Solver`, which goes deep first.
+//
+// Duplicates are detected late. The successors of a whole layer are collected into sorted
+// runs of at most `memory_limit` states, then merged against the sorted file of visited
+// states, which leaves the next layer. Every layer is kept until the search ends, so the
+// path to a won state can be traced back one layer at a time.
+pub struct ExternalSolver {
+    config: SearchConfig,
+    dir: PathBuf,
+    // Successors collected in memory before they are sorted and spilled to disk
+    memory_limit: usize,
+    stats: SearchStats,
+}
+
+impl ExternalSolver {
+    // Keeps its files in a directory of its own under `dir`, removed when the search ends
+    pub fn new(config: SearchConfig, dir: impl AsRef<Path>) -> Self {
+        let dir = dir.as_ref().join(format!(
+            "solitaire-{}-{}-{}",
+            config.deal.seed,
+            std::process::id(),
+            NEXT_SEARCH_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        Self {
+            config,
+            dir,
+            memory_limit: successors_in(DEFAULT_MEMORY_LIMIT),
+            stats: SearchStats::default(),
+        }
+    }
+
+    // Bytes of successors collected in memory before they are sorted and spilled to disk
+    pub fn with_memory_limit(mut self, memory_bytes: usize) -> Self {
+        self.memory_limit = successors_in(memory_bytes);
+        self
+    }
+
+    pub fn solve(mut self) -> SolveResult {
+        let timer = Instant::now();
+        debug!(
+            "Solving seed {} with files in {}:\n{}",
+            self.config.deal.seed,
+            self.dir.display(),
+            self.config.original_game
+        );
+        let search = fs::create_dir_all(&self.dir).and_then(|()| self.search(timer));
+        if let Err(err) = fs::remove_dir_all(&self.dir) {
+            error!("Could not remove {}: {}", self.dir.display(), err);
+        }
+        match search {
+            Ok(None) => self.result(Outcome::Unsolvable, Vec::new(), None, timer.elapsed()),
+            Ok(Some(Stop::Solved(solution))) => {
+                self.result(Outcome::Solved, solution, None, timer.elapsed())
+            }
+            Ok(Some(Stop::Budget(outcome))) => {
+                self.result(outcome, Vec::new(), None, timer.elapsed())
+            }
+            Err(err) => {
+                error!("Seed {}: {}", self.config.deal.seed, err);
+                let error = Some(format!("external search failed: {}", err));
+                self.result(Outcome::Error, Vec::new(), error, timer.elapsed())
+            }
+        }
+    }
+
+    fn layer_path(&self, depth: u32) -> PathBuf {
+        self.dir.join(format!("layer-{}.bin", depth))
+    }
+
+    fn run_path(&self, run: usize) -> PathBuf {
+        self.dir.join(format!("run-{}.bin", run))
+    }
+
+    fn visited_path(&self, depth: u32) -> PathBuf {
+        self.dir.join(format!("visited-{}.bin", depth))
+    }
+
+    fn search(&mut self, timer: Instant) -> io::Result<Option<Stop>> {
+        let mut layer = RecordWriter::create(&self.layer_path(0))?;
+        layer.write(&self.config.original_game)?;
+        let mut layer_len = layer.finish()?;
+        let mut visited = RecordWriter::create(&self.visited_path(0))?;
+        visited.write(&self.config.original_game.compact_state())?;
+        self.stats.peak_visited_size = visited.finish()?;
+
+        let mut depth = 0;
+        while layer_len > 0 {
+            debug!(
+                "Seed {}: layer {} has {} states, {} visited",
+                self.config.deal.seed, depth, layer_len, self.stats.peak_visited_size
+            );
+            self.stats.max_depth = depth;
+            self.stats.peak_frontier_size = self.stats.peak_frontier_size.max(layer_len);
+            let runs = match self.expand_layer(depth, timer)? {
+                Ok(runs) => runs,
+                Err(stop) => return Ok(Some(stop)),
+            };
+            layer_len = self.merge_runs(depth, runs)?;
+            fs::remove_file(self.visited_path(depth))?;
+            depth += 1;
+        }
+        Ok(None)
+    }
+
+    // Expands every state of a layer into sorted runs of successors, returning how many runs
+    // were written, or why the search stops
+    fn expand_layer(&mut self, depth: u32, timer: Instant) -> io::Result<Result<usize, Stop>> {
+        let mut layer = RecordReader::<Game>::open(&self.layer_path(depth))?;
+        let mut successors = Vec::new();
+        let mut runs = 0;
+        while let Some(game) = layer.next()? {
+            self.stats.expanded_nodes += 1;
+            let valid_moves = game.moves(self.config.move_generation);
+            if Solver::is_game_lost(&valid_moves) {
+                self.stats.dead_ends += 1;
+            } else if self.config.prune_deadlocks && game.is_deadlocked() {
+                self.stats.deadlocks += 1;
+            } else {
+                for mv in valid_moves {
+                    let next_game = game
+                        .handle_move(&mv)
+                        .map_err(|err| io::Error::other(format!("{} failed: {}", mv, err)))?;
+                    self.stats.generated_nodes += 1;
+                    if next_game.is_game_won() {
+                        let mut solution = self.trace_back(depth, &game)?;
+                        solution.push(mv);
+                        return Ok(Err(Stop::Solved(solution)));
+                    }
+                    successors.push((next_game.compact_state(), next_game));
+                }
+            }
+            if successors.len() >= self.memory_limit {
+                self.write_run(runs, &mut successors)?;
+                runs += 1;
+            }
+
+            if self
+                .config
+                .is_out_of_budget(self.stats.expanded_nodes, timer.elapsed())
+            {
+                return Ok(Err(Stop::Budget(Outcome::Timeout)));
+            }
+            if self.config.is_cancelled() {
+                return Ok(Err(Stop::Budget(Outcome::Cancelled)));
+            }
+        }
+        if !successors.is_empty() {
+            self.write_run(runs, &mut successors)?;
+            runs += 1;
+        }
+        Ok(Ok(runs))
+    }
+
+    fn write_run(
+        &mut self,
+        run: usize,
+        successors: &mut Vec<(GameCompact, Game)>,
+    ) -> io::Result<()> {
+        successors.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        let len = successors.len();
+        successors.dedup_by(|a, b| a.0 == b.0);
+        self.stats.duplicates += (len - successors.len()) as u64;
+        let mut writer = RecordWriter::create(&self.run_path(run))?;
+        for successor in successors.drain(..) {
+            writer.write(&successor)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    // Merges the runs of successors of layer `depth` against the states visited so far. The
+    // states seen for the first time make up the next layer and join the visited states.
+    // Returns the size of the next layer.
+    fn merge_runs(&mut self, depth: u32, runs: usize) -> io::Result<usize> {
+        let mut readers = (0..runs)
+            .map(|run| RecordReader::<(GameCompact, Game)>::open(&self.run_path(run)))
+            .collect::<io::Result<Vec<_>>>()?;
+        let mut heads = BinaryHeap::new();
+        let mut games = Vec::with_capacity(runs);
+        for (run, reader) in readers.iter_mut().enumerate() {
+            let head = reader.next()?;
+            if let Some((key, _)) = &head {
+                heads.push(Reverse((key.clone(), run)));
+            }
+            games.push(head.map(|(_, game)| game));
+        }
+
+        let mut visited = RecordReader::<GameCompact>::open(&self.visited_path(depth))?;
+        let mut visited_head = visited.next()?;
+        let mut next_visited = RecordWriter::create(&self.visited_path(depth + 1))?;
+        let mut next_layer = RecordWriter::create(&self.layer_path(depth + 1))?;
+        let mut last_key: Option<GameCompact> = None;
+        while let Some(Reverse((key, run))) = heads.pop() {
+            let game = games[run].take().expect("run head without a game");
+            if let Some((next_key, next_game)) = readers[run].next()? {
+                heads.push(Reverse((next_key, run)));
+                games[run] = Some(next_game);
+            }
+            if last_key.as_ref() == Some(&key) {
+                self.stats.duplicates += 1;
+                continue;
+            }
+            // Copy over the visited states that sort before this one
+            while let Some(visited_key) = visited_head.take_if(|visited_key| *visited_key < key) {
+                next_visited.write(&visited_key)?;
+                visited_head = visited.next()?;
+            }
+            if visited_head.as_ref() == Some(&key) {
+                self.stats.duplicates += 1;
+            } else {
+                next_visited.write(&key)?;
+                next_layer.write(&game)?;
+            }
+            last_key = Some(key);
+        }
+        while let Some(visited_key) = visited_head {
+            next_visited.write(&visited_key)?;
+            visited_head = visited.next()?;
+        }
+        for run in 0..runs {
+            fs::remove_file(self.run_path(run))?;
+        }
+        self.stats.peak_visited_size = next_visited.finish()?;
+        next_layer.finish()
+    }
+
+    // The moves from the dealt game to `game`, a state of layer `depth`. Every state was
+    // written to its layer exactly as it came out of a state of the layer before, with the
+    // move that produced it, so that state can be found by replaying the move.
+    fn trace_back(&self, depth: u32, game: &Game) -> io::Result<Vec<Move>> {
+        let mut solution = Vec::new();
+        let mut target = game.clone();
+        for depth in (0..depth).rev() {
+            let mv = target
+                .prev_move
+                .expect("state past the deal without a move");
+            let mut layer = RecordReader::<Game>::open(&self.layer_path(depth))?;
+            let parent = loop {
+                let Some(candidate) = layer.next()? else {
+                    return Err(io::Error::other(format!(
+                        "no state of layer {} leads to the state reached by {}",
+                        depth, mv
+                    )));
+                };
+                if candidate.moves(self.config.move_generation).contains(&mv)
+                    && candidate.handle_move(&mv).is_ok_and(|next| next == target)
+                {
+                    break candidate;
+                }
+            };
+            solution.push(mv);
+            target = parent;
+        }
+        solution.reverse();
+        Ok(solution)
+    }
+
+    fn result(
+        self,
+        outcome: Outcome,
+        solution: Vec<Move>,
+        error: Option<String>,
+        elapsed: Duration,
+    ) -> SolveResult {
+        SolveResult {
+            error,
+            ..self.config.result(
+                outcome,
+                solution,
+                self.stats,
+                elapsed,
+                Deduplication::Exact,
+                Strategy::Bfs,
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        deal::Deal,
+        rules::Rules,
+        solver::{tests::near_the_end, Budget},
+    };
+
+    fn solver(seed: u32) -> ExternalSolver {
+        let config = SearchConfig::from_deal(Deal::from_seed(seed), Rules::default())
+            .with_budget(Budget::unlimited());
+        // Small enough to spill every layer into several runs
+        ExternalSolver::new(config, std::env::temp_dir())
+            .with_memory_limit(7 * mem::size_of::<(GameCompact, Game)>())
+    }
+
+    #[test]
+    fn visits_the_same_states_as_the_in_memory_search() {
+        let in_memory = Solver::from_deal(Deal::from_seed(282), Rules::default()).solve();
+        let external = solver(282);
+        let dir = external.dir.clone();
+        let result = external.solve();
+        assert_eq!(result.outcome, Outcome::Unsolvable);
+        assert_eq!(
+            result.stats.peak_visited_size,
+            in_memory.stats.peak_visited_size
+        );
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn traces_back_a_shortest_solution() {
+        let mid_game = near_the_end(12);
+        let mut external = solver(0);
+        external.config.original_game = mid_game.clone();
+        let result = external.solve();
+
+        assert_eq!(result.outcome, Outcome::Solved);
+        assert!(result.solution.len() <= 12);
+        let end = result
+            .solution
+            .iter()
+            .try_fold(mid_game, |game, mv| game.handle_move(mv))
+            .unwrap();
+        assert!(end.is_game_won());
+    }
+
+    #[test]
+    fn searches_of_the_same_deal_use_their_own_directories() {
+        assert_ne!(solver(0).dir, solver(0).dir);
+    }
+}
@@ -0,0 +1,493 @@
+pub mod batch;
+pub mod bloom;
+pub mod card;
+pub mod deadlock;
+pub mod deal;
+pub mod external;
+pub mod mcts;
+pub mod moves;
+pub mod parallel;
+pub mod perft;
+pub mod report;
+pub mod results;
+pub mod rules;
+pub mod solver;
+pub mod strategy;
+pub mod validate;
+
+use arrayvec::ArrayVec;
+use card::*;
+use deal::*;
+use moves::*;
+use rules::*;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, fmt};
+use validate::{InvalidState, Violation};
+
+#[derive(Default, Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CardStack<const CAP: usize>(pub ArrayVec<Card, CAP>);
+
+impl<const CAP: usize> CardStack<CAP> {
+    pub fn score(&self) -> u8 {
+        *self.0.first().unwrap_or(&u8::MAX)
+    }
+
+    pub fn flip_face_up(&mut self) {
+        if !self.0.is_empty() {
+            *self.0.last_mut().unwrap() = self.0.last().unwrap().face_up();
+        }
+    }
+}
+
+impl<const CAP: usize> PartialOrd for CardStack<CAP> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<const CAP: usize> Ord for CardStack<CAP> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score().cmp(&other.score())
+    }
+}
+
+#[derive(Default, Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Game {
+    tableaus: [CardStack<MAX_TABLEAU_LEN>; NUM_TABLEAUS],
+    first_unlocked_idx: [u8; NUM_TABLEAUS],
+    foundations: [Card; 4],
+    foundation_stack: u64,
+    // TODO: Some optimizations in stock and waste
+    pub stock: CardStack<52>,
+    waste: CardStack<52>,
+    prev_move: Option<Move>,
+    pub rules: Rules,
+    pub random_seed: u32,
+}
+
+impl Game {
+    // Deals a deal that is known to be valid, like one made from a seed
+    pub fn from_deal(deal: &Deal, rules: Rules) -> Self {
+        Self::try_from_deal(deal, rules)
+            .unwrap_or_else(|err| panic!("Invalid deal {}: {}", deal.seed, err))
+    }
+
+    // Deals a deal read from outside, reporting what is wrong with its cards if they don't
+    // make a deck
+    pub fn try_from_deal(deal: &Deal, rules: Rules) -> Result<Self, InvalidState> {
+        // Dealing takes every position of the deck, so a short one can't even be dealt
+        if deal.cards.len() < NUM_CARDS_DECK as usize {
+            let missing = (0..NUM_CARDS_DECK).filter(|card| !deal.cards.contains(card));
+            return Err(InvalidState(missing.map(Violation::MissingCard).collect()));
+        }
+        let mut game = Game {
+            foundations: [u8::MAX; 4],
+            stock: CardStack(deal.cards.clone()),
+            rules,
+            random_seed: deal.seed,
+            ..Default::default()
+        };
+        game.initial_deal();
+        game.validate()?;
+        Ok(game)
+    }
+
+    fn sort_tableaus(&self) -> Self {
+        let mut new_game = self.clone();
+        // This trick helps reduce the problem space by eliminating symmetrical setups
+        new_game.tableaus.sort();
+        // we need to reset first unlocked when we sort.
+        for idx in 0..NUM_TABLEAUS {
+            new_game.set_first_unlocked_index(idx)
+        }
+        new_game
+    }
+
+    fn set_first_unlocked_index(&mut self, tableau_idx: usize) {
+        self.first_unlocked_idx[tableau_idx] = self.compute_first_unlocked_index(tableau_idx);
+    }
+
+    fn compute_first_unlocked_index(&self, tableau_idx: usize) -> u8 {
+        if self.tableaus[tableau_idx].0.is_empty() {
+            u8::MAX
+        } else {
+            for (card_idx, _) in self.tableaus[tableau_idx].0.iter().enumerate().rev() {
+                if !self.is_card_unlocked(tableau_idx, card_idx) {
+                    return card_idx as u8 + 1;
+                }
+            }
+            0
+        }
+    }
+
+    fn initial_deal(&mut self) {
+        self.tableaus[0]
+            .0
+            .try_extend_from_slice(&[self.stock.0[51]])
+            .expect("Could not extend tableau");
+        self.tableaus[1]
+            .0
+            .try_extend_from_slice(&[self.stock.0[51 - 1].face_down(), self.stock.0[51 - 7]])
+            .expect("Could not  extend tableau");
+        self.tableaus[2]
+            .0
+            .try_extend_from_slice(&[
+                self.stock.0[51 - 2].face_down(),
+                self.stock.0[51 - 8].face_down(),
+                self.stock.0[51 - 13],
+            ])
+            .expect("Could not extend tableau");
+        self.tableaus[3]
+            .0
+            .try_extend_from_slice(&[
+                self.stock.0[51 - 3].face_down(),
+                self.stock.0[51 - 9].face_down(),
+                self.stock.0[51 - 14].face_down(),
+                self.stock.0[51 - 18],
+            ])
+            .expect("Could not extend tableau");
+        self.tableaus[4]
+            .0
+            .try_extend_from_slice(&[
+                self.stock.0[51 - 4].face_down(),
+                self.stock.0[51 - 10].face_down(),
+                self.stock.0[51 - 15].face_down(),
+                self.stock.0[51 - 19].face_down(),
+                self.stock.0[51 - 22],
+            ])
+            .expect("Could not extend tableau");
+        self.tableaus[5]
+            .0
+            .try_extend_from_slice(&[
+                self.stock.0[51 - 5].face_down(),
+                self.stock.0[51 - 11].face_down(),
+                self.stock.0[51 - 16].face_down(),
+                self.stock.0[51 - 20].face_down(),
+                self.stock.0[51 - 23].face_down(),
+                self.stock.0[51 - 25],
+            ])
+            .expect("Could not extend tableau");
+        self.tableaus[6]
+            .0
+            .try_extend_from_slice(&[
+                self.stock.0[51 - 6].face_down(),
+                self.stock.0[51 - 12].face_down(),
+                self.stock.0[51 - 17].face_down(),
+                self.stock.0[51 - 21].face_down(),
+                self.stock.0[51 - 24].face_down(),
+                self.stock.0[51 - 26].face_down(),
+                self.stock.0[51 - 27],
+            ])
+            .expect("Could not extend tableau");
+        for tableau_idx in 0..NUM_TABLEAUS {
+            self.set_first_unlocked_index(tableau_idx);
+        }
+        self.stock.0.truncate(NUM_CARDS_DECK as usize - 28);
+    }
+
+    //
+    // Logic Checks
+    //
+
+    fn can_be_placed_on_top_of(&self, bottom: Card, top: Card) -> bool {
+        are_card_ranks_descending(bottom, top)
+            && (self.rules.relaxation.ignore_colors || are_card_colors_different(bottom, top))
+    }
+
+    fn can_go_to_empty_tableau(&self, card: Card) -> bool {
+        self.rules.relaxation.any_card_on_empty_tableau || is_king(card)
+    }
+
+    fn can_move_card_to_foundation(&self, card: Card) -> bool {
+        let top_foundation_card = self.foundations[suit_rank(card) as usize];
+        if top_foundation_card != u8::MAX {
+            are_card_ranks_ascending(top_foundation_card, card)
+                && are_card_suits_the_same(top_foundation_card, card)
+        } else {
+            card_rank(card) == 1
+        }
+    }
+
+    fn is_card_unlocked(&self, tableau_idx: usize, card_idx: usize) -> bool {
+        if card_idx == self.tableaus[tableau_idx].0.len() - 1 {
+            true
+        } else {
+            let card = self.tableaus[tableau_idx].0[card_idx];
+            let card_above = self.tableaus[tableau_idx].0[card_idx + 1];
+            card.is_face_up() && self.can_be_placed_on_top_of(card, card_above)
+        }
+    }
+
+    pub fn is_game_won(&self) -> bool {
+        self.foundations
+            .iter()
+            .fold(0, |acc, card| acc + card_rank(*card) as usize)
+            == ranking_of_kings()
+    }
+
+    //
+    // Actions
+    //
+    pub fn handle_move(&self, mv: &Move) -> Result<Self, MoveError> {
+        let Move { from, to } = mv;
+        let mut game = match (from, to) {
+            (CardPosition::Stock, CardPosition::Waste) => {
+                Ok(self.draw_from_stock(self.rules.draw_count as usize))
+            }
+            (CardPosition::Waste, CardPosition::Stock) => Ok(self.restock()),
+            (CardPosition::Waste, CardPosition::Foundation(_)) => {
+                Ok(self.move_from_waste_to_foundation())
+            }
+            (CardPosition::Waste, CardPosition::Tableau((tableau_idx, _))) => {
+                self.move_from_waste_to_tableau(*tableau_idx)
+            }
+            (CardPosition::Tableau((tableau_idx, _)), CardPosition::Foundation(_)) => {
+                Ok(self.move_from_tableau_to_foundation(*tableau_idx))
+            }
+            (
+                CardPosition::Tableau((from_tableau_idx, card_idx)),
+                CardPosition::Tableau((to_tableau_idx, _)),
+            ) => self.move_stack_between_tableaus(*from_tableau_idx, *card_idx, *to_tableau_idx),
+            _ => unreachable!(),
+        }?;
+        game.prev_move = Some(*mv);
+        // Checking every new state is costly, so it only happens when debugging
+        if log::log_enabled!(log::Level::Debug) {
+            if let Err(err) = game.validate() {
+                log::error!("{} after {}:\n{}", err, mv, game);
+                return Err(err.into());
+            }
+        }
+        Ok(game)
+    }
+
+    fn restock(&self) -> Self {
+        let mut new_game = self.clone();
+        new_game.waste.0.reverse();
+        std::mem::swap(&mut new_game.stock, &mut new_game.waste);
+        new_game
+    }
+
+    fn draw_from_stock(&self, count: usize) -> Self {
+        let mut new_game = self.clone();
+        let count = count.min(new_game.stock.0.len());
+        (0..count).for_each(|_| {
+            new_game
+                .waste
+                .0
+                .push(new_game.stock.0.pop().expect("Popped empty stock"));
+        });
+        new_game
+    }
+
+    fn move_from_waste_to_foundation(&self) -> Self {
+        let mut new_game = self.clone();
+        let card = new_game.waste.0.pop().expect("Popped empty waste");
+        new_game.foundations[suit_rank(card) as usize] = card;
+        new_game.foundation_stack |= 1 << card;
+        new_game
+    }
+
+    fn move_from_tableau_to_foundation(&self, tableau_idx: u8) -> Self {
+        let mut new_game = self.clone();
+        let card = new_game.tableaus[tableau_idx as usize]
+            .0
+            .pop()
+            .expect("Popped empty tableau");
+        new_game.tableaus[tableau_idx as usize].flip_face_up();
+        new_game.foundations[suit_rank(card) as usize] = card;
+
+        new_game.set_first_unlocked_index(tableau_idx as usize);
+
+        new_game.foundation_stack |= 1 << card;
+        new_game
+    }
+
+    fn move_from_waste_to_tableau(&self, tableau_idx: u8) -> Result<Self, MoveError> {
+        self.check_tableau_room(tableau_idx, 1)?;
+        let mut new_game = self.clone();
+        new_game.tableaus[tableau_idx as usize]
+            .0
+            .push(new_game.waste.0.pop().expect("Popped empty waste"));
+
+        if new_game.tableaus[tableau_idx as usize].0.len() == 1 {
+            new_game.set_first_unlocked_index(tableau_idx as usize);
+        }
+        Ok(new_game)
+    }
+
+    fn move_stack_between_tableaus(
+        &self,
+        from_index: u8,
+        card_idx: u8,
+        to_index: u8,
+    ) -> Result<Self, MoveError> {
+        let moved = self.tableaus[from_index as usize].0.len() - card_idx as usize;
+        self.check_tableau_room(to_index, moved)?;
+        let mut new_game = self.clone();
+        let drain_iter = new_game.tableaus[from_index as usize]
+            .0
+            .drain((card_idx as usize)..)
+            .collect::<Vec<_>>();
+
+        new_game.tableaus[to_index as usize].0.extend(drain_iter);
+        new_game.tableaus[from_index as usize]
+            .0
+            .truncate(card_idx as usize);
+        new_game.tableaus[from_index as usize].flip_face_up();
+
+        new_game.set_first_unlocked_index(from_index as usize);
+        new_game.set_first_unlocked_index(to_index as usize);
+
+        Ok(new_game)
+    }
+
+    // Fails if `count` more cards would not fit on the tableau
+    fn check_tableau_room(&self, tableau_idx: u8, count: usize) -> Result<(), MoveError> {
+        let len = self.tableaus[tableau_idx as usize].0.len() + count;
+        if len > MAX_TABLEAU_LEN {
+            return Err(MoveError::TableauOverflow {
+                tableau: tableau_idx,
+                len,
+                capacity: MAX_TABLEAU_LEN,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "================== SEED: {:?} ==================",
+            self.random_seed
+        )?;
+        writeln!(f, "--------- Foundations ---------")?;
+        self.foundations
+            .iter()
+            .try_for_each(|card| write!(f, "[{}]\t", pretty_string(*card)))?;
+        writeln!(f)?;
+        writeln!(f, "--------- Tableaus ------------")?;
+        self.tableaus
+            .iter()
+            .enumerate()
+            .try_for_each(|(idx, tableau)| {
+                write!(f, "{}:\t", idx)?;
+                tableau
+                    .0
+                    .iter()
+                    .try_for_each(|card| write!(f, "{}\t", pretty_string(*card)))?;
+                writeln!(f)
+            })?;
+        writeln!(f, "--------- Unlocked ------------")?;
+        self.first_unlocked_idx
+            .iter()
+            .try_for_each(|idx| write!(f, "{:?}\t", idx))?;
+        writeln!(f)?;
+        writeln!(f, "--------- Stock ---------------")?;
+        self.stock
+            .0
+            .iter()
+            .try_for_each(|card| write!(f, "{} ", pretty_string(*card)))?;
+        writeln!(f)?;
+        writeln!(f, "--------- Waste ---------------")?;
+        self.waste
+            .0
+            .iter()
+            .try_for_each(|card| write!(f, "{} ", pretty_string(*card)))?;
+        writeln!(f)?;
+        // The alternate form `{:#}` also lists the moves that can be made from this state
+        if f.alternate() {
+            writeln!(f, "--------- Valid Moves ---------")?;
+            self.valid_moves()
+                .iter()
+                .try_for_each(|mv| writeln!(f, "{}", mv.pretty_string(self)))?;
+        }
+        writeln!(f, "--------- Prev Move -----------")?;
+        writeln!(f, "{:?}", self.prev_move)
+    }
+}
+
+// TODO: Reduce symmetry in suit permutation
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A game whose tableau 0 is filled to `len` cards, with the cards taken from the stock
+    fn game_with_tall_tableau(len: usize) -> Game {
+        let mut game = Game::from_deal(&Deal::from_seed(0), Rules::default());
+        game.tableaus[0].0.clear();
+        for _ in 0..len {
+            let card = game.stock.0.pop().unwrap();
+            game.tableaus[0].0.push(card);
+        }
+        game
+    }
+
+    #[test]
+    fn broken_deals_are_reported() {
+        let mut deal = Deal::from_seed(0);
+        let last = deal.cards.pop().unwrap();
+        assert_eq!(
+            Game::try_from_deal(&deal, Rules::default()),
+            Err(InvalidState(vec![Violation::MissingCard(last)]))
+        );
+        deal.cards.push(deal.cards[0]);
+        let err = Game::try_from_deal(&deal, Rules::default()).unwrap_err();
+        assert!(err.0.contains(&Violation::MissingCard(last)));
+        assert!(err.0.contains(&Violation::DuplicateCard {
+            card: deal.cards[0],
+            count: 2
+        }));
+    }
+
+    #[test]
+    fn stack_move_past_capacity_is_an_error() {
+        let capacity = MAX_TABLEAU_LEN;
+        let game = game_with_tall_tableau(capacity - 1);
+        let from_len = game.tableaus[6].0.len() as u8;
+        let mv = Move {
+            from: CardPosition::Tableau((6, from_len - 2)),
+            to: CardPosition::Tableau((0, capacity as u8 - 2)),
+        };
+        match game.handle_move(&mv) {
+            Err(MoveError::TableauOverflow {
+                tableau,
+                len,
+                capacity: overflowed,
+            }) => {
+                assert_eq!(tableau, 0);
+                assert_eq!(len, capacity + 1);
+                assert_eq!(overflowed, capacity);
+            }
+            other => panic!("expected an overflow, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn stack_move_up_to_capacity_is_played() {
+        let capacity = MAX_TABLEAU_LEN;
+        let game = game_with_tall_tableau(capacity - 1);
+        let from_len = game.tableaus[6].0.len() as u8;
+        let mv = Move {
+            from: CardPosition::Tableau((6, from_len - 1)),
+            to: CardPosition::Tableau((0, capacity as u8 - 2)),
+        };
+        let game = game.handle_move(&mv).unwrap();
+        assert_eq!(game.tableaus[0].0.len(), capacity);
+    }
+
+    #[test]
+    fn waste_move_onto_full_tableau_is_an_error() {
+        let capacity = MAX_TABLEAU_LEN;
+        let game = game_with_tall_tableau(capacity);
+        let game = game.handle_move(&"S".parse().unwrap()).unwrap();
+        let mv = "W-T0:0".parse().unwrap();
+        assert!(matches!(
+            game.handle_move(&mv),
+            Err(MoveError::TableauOverflow { tableau: 0, .. })
+        ));
+    }
+}
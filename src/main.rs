@@ -1,415 +1,605 @@
-mod card;
-mod moves;
-mod solver;
-
-use arrayvec::ArrayVec;
-use card::*;
-use mersenne_twister::MT19937;
-use moves::*;
-use rand::{Rng, SeedableRng};
+use clap::{error::ErrorKind, ArgAction, Args, CommandFactory, Parser, Subcommand};
+use log::{warn, LevelFilter};
 use rayon::prelude::*;
-use serde::Serialize;
-use solver::*;
-use std::{cmp::Ordering, fmt};
-
-const VERBOSE_PRINT: bool = false;
-const DEBUG: bool = false;
-
-#[derive(Default, Debug, Clone, Hash, PartialEq, Eq)]
-pub struct CardStack<const CAP: usize>(pub ArrayVec<Card, CAP>);
-
-impl<const CAP: usize> CardStack<CAP> {
-    pub fn score(&self) -> u8 {
-        *self.0.first().unwrap_or(&u8::MAX)
-    }
+use solitaire_solvability::{
+    batch::{
+        completed_deals, pending_seeds, read_seed_list, retain_resolved, solve_with_escalation,
+        SeedRange, Shard,
+    },
+    deal::Deal,
+    external::{ExternalSolver, DEFAULT_MEMORY_LIMIT},
+    mcts::{MctsPlayer, Rollout, DEFAULT_EXPLORATION, DEFAULT_ITERATIONS},
+    moves::MoveGeneration,
+    parallel::ParallelSolver,
+    report::{wilson_interval, OutcomeCounts, Report},
+    results::{read_results, JsonLinesWriter, Outcome, SolveResult},
+    rules::{Relaxation, Rules},
+    solver::{
+        Budget, CancellationToken, CheckpointError, SearchConfig, SearchStats, Solver,
+        DEFAULT_RELAXED_CHECK_NODES, DEFAULT_TIME_LIMIT_MS,
+    },
+    strategy::Strategy,
+    Game,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+#[derive(Parser)]
+#[command(about = "Estimates how many Klondike deals are solvable")]
+struct Cli {
+    /// Log more: once for a summary of every deal, twice to also check the game state after
+    /// every move, three times to trace every state searched. RUST_LOG overrides this.
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+    #[command(subcommand)]
+    command: Command,
+}
 
-    pub fn flip_face_up(&mut self) {
-        if !self.0.is_empty() {
-            *self.0.last_mut().unwrap() = self.0.last().unwrap().face_up();
-        }
-    }
+#[derive(Subcommand)]
+enum Command {
+    /// Solve every deal in a seed range, appending one JSON record per deal as it finishes.
+    /// Seeds already recorded in the output file are skipped, so an interrupted batch can be
+    /// resumed or extended by running it again.
+    Batch(BatchArgs),
+    /// Re-run timed-out deals with a budget that grows geometrically up to a cap, recording
+    /// the budget each deal resolved at
+    Retry(RetryArgs),
+    /// Solve a single deal, printing progress as the search goes and the result at the end.
+    /// Ctrl-C stops the search and prints the partial result.
+    Solve(SolveArgs),
+    /// Summarize batch results: outcome rates with confidence intervals, histograms of
+    /// solution lengths and states visited, and timing percentiles
+    Report(ReportArgs),
+    /// Count the move sequences of every length up to a depth from a deal, and the positions
+    /// they reach, to check that move generation hasn't changed
+    Perft(PerftArgs),
+    /// Play deals with Monte Carlo tree search, printing one JSON record per game with the
+    /// moves played and whether it was won, and the win rate at the end. Proves nothing, but
+    /// plays deals too large to solve and gives a baseline to compare the solver with.
+    Play(PlayArgs),
 }
 
-impl<const CAP: usize> PartialOrd for CardStack<CAP> {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.score().cmp(&other.score()))
-    }
+#[derive(Args)]
+struct BudgetArgs {
+    /// Seconds a single deal may be searched before it is recorded as timed out
+    #[arg(long, default_value_t = DEFAULT_TIME_LIMIT_MS as f64 / 1000.0)]
+    time_limit: f64,
+    /// Number of states a single deal may visit before it is recorded as timed out
+    #[arg(long)]
+    max_nodes: Option<u64>,
 }
 
-impl<const CAP: usize> Ord for CardStack<CAP> {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.score().cmp(&other.score())
+impl BudgetArgs {
+    fn budget(&self) -> Budget {
+        Budget {
+            time_limit_ms: (self.time_limit * 1000.0) as u64,
+            max_nodes: self.max_nodes,
+        }
     }
 }
 
-#[derive(Default, Debug, Clone, Hash, PartialEq, Eq)]
-pub struct Game {
-    tableaus: [CardStack<20>; 7],
-    first_unlocked_idx: [u8; 7],
-    foundations: [Card; 4],
-    foundation_stack: u64,
-    // TODO: Some optimizations in stock and waste
-    pub stock: CardStack<52>,
-    waste: CardStack<52>,
-    prev_move: Option<Move>,
-    random_engine: MT19937,
-    pub random_seed: u32,
+// How the solver searches, shared by every command that runs it
+#[derive(Args)]
+struct SearchArgs {
+    /// Moves to search: `pruned` only splits a tableau stack to uncover a card that can go to
+    /// a foundation, `complete` tries every legal move so that unsolvable deals are proven so
+    #[arg(long, default_value_t = MoveGeneration::default())]
+    moves: MoveGeneration,
+    /// Order to expand states in: `dfs`, `bfs`, `best-first` (closest to a win first),
+    /// `iterative-deepening` or `beam[:<width>]`. A beam search proves no deal unsolvable.
+    #[arg(long, default_value_t = Strategy::default())]
+    strategy: Strategy,
+    /// Expand the states in which some cards can never leave their tableau rather than
+    /// stopping there, to measure how much searching the check saves
+    #[arg(long)]
+    no_deadlock_pruning: bool,
+    /// Search each deal under relaxed rules first, a comma separated list of `colors` (any
+    /// card goes on the next rank) and `empty-tableau` (any card goes on an empty tableau).
+    /// Deals that can't be won even so are proven unsolvable without the full search.
+    #[arg(long)]
+    relaxed_check: Option<Relaxation>,
+    /// Number of states the relaxed search may expand before the full search takes over
+    #[arg(long, default_value_t = DEFAULT_RELAXED_CHECK_NODES, requires = "relaxed_check")]
+    relaxed_check_nodes: u64,
 }
 
-impl Game {
-    fn new() -> Self {
-        let mut game = Game::default();
-        game.foundations = [u8::MAX; 4];
-        game.set_stock();
-        game.initial_deal();
-        game.validate();
-        game
+impl SearchArgs {
+    // The settings every solver of `deal` shares, as these arguments say
+    fn config(&self, deal: Deal, budget: Budget, cancellation: CancellationToken) -> SearchConfig {
+        SearchConfig::from_deal(deal, Rules::default())
+            .with_budget(budget)
+            .with_move_generation(self.moves)
+            .with_deadlock_pruning(!self.no_deadlock_pruning)
+            .with_cancellation(cancellation)
     }
 
-    fn validate(&self) {
-        let mut game_stack: u64 = 0;
-        for card in &self.stock.0 {
-            game_stack |= 1 << card.face_up();
-        }
-        for card in &self.waste.0 {
-            game_stack |= 1 << card.face_up();
-        }
-        for tableau in &self.tableaus {
-            for card in &tableau.0 {
-                game_stack |= 1 << card.face_up();
-            }
-        }
-        let full_game_stack = self.foundation_stack | game_stack;
-        if full_game_stack != 0b0000000000001111111111111111111111111111111111111111111111111111 {
-            println!("Invalid Game State:\n{}", self);
-            let missing_bit = full_game_stack
-                ^ 0b0000000000001111111111111111111111111111111111111111111111111111;
-            let card = (missing_bit as f64).log2() as u32;
-            println!("Missing card: {}", pretty_string(card as u8));
-            panic!("Invalid state");
+    // The single-threaded solver of `config`, searching as these arguments say
+    fn solver(&self, config: SearchConfig) -> Solver {
+        let solver = Solver::new(config).with_strategy(self.strategy);
+        match self.relaxed_check {
+            Some(relaxation) => solver.with_relaxed_check(relaxation, self.relaxed_check_nodes),
+            None => solver,
         }
     }
+}
 
-    fn sort_tableaus(&self) -> Self {
-        let mut new_game = self.clone();
-        // This trick helps reduce the problem space by eliminating symmetrical setups
-        new_game.tableaus.sort();
-        // we need to reset first unlocked when we sort.
-        for idx in 0..7 {
-            new_game.set_first_unlocked_index(idx)
-        }
-        new_game
-    }
+#[derive(Args)]
+struct BatchArgs {
+    /// Seeds of the deals to solve, end excluded
+    #[arg(long, default_value = "0..10000", conflicts_with = "from_list")]
+    seeds: SeedRange,
+    /// Solve the seeds listed in this file instead, one per line, like a retry list. Only
+    /// solved and unsolvable deals of the output file are skipped, so the deals of a retry
+    /// list are searched again even if they are recorded as timed out.
+    #[arg(long)]
+    from_list: Option<PathBuf>,
+    /// Only solve the seeds of shard `i` out of `n`, to split a range between processes
+    #[arg(long, default_value_t = Shard::default())]
+    shard: Shard,
+    #[command(flatten)]
+    budget: BudgetArgs,
+    #[command(flatten)]
+    search: SearchArgs,
+    /// Keep the visited states of each deal in a Bloom filter of this many MiB instead of a
+    /// hash set. Searches more states in the same memory, but unsolvable results are not
+    /// proofs.
+    #[arg(long)]
+    bloom_memory: Option<f64>,
+    /// JSON Lines file the results are appended to
+    #[arg(long, default_value = "results.jsonl")]
+    output: PathBuf,
+    /// File the seeds of timed-out deals are appended to, to be re-run with a larger budget
+    #[arg(long, default_value = "retry.txt")]
+    retry_list: PathBuf,
+    /// Stop once the confidence interval on the solvable rate, counting the deals already in
+    /// the output file, is narrower than this width (e.g. 0.01 for one percentage point)
+    #[arg(long)]
+    target_width: Option<f64>,
+    /// Normal quantile of the confidence interval, 1.96 for 95%
+    #[arg(long, default_value_t = 1.96)]
+    z: f64,
+}
 
-    fn set_stock(&mut self) {
-        let mut rng = rand::thread_rng();
-        self.random_seed = rng.gen();
-        self.random_engine.reseed(self.random_seed);
-        self.stock.0 = (0..NUM_CARDS_DECK).collect::<ArrayVec<Card, 52>>();
-        self.random_engine.shuffle(&mut self.stock.0);
-    }
+#[derive(Args)]
+struct RetryArgs {
+    /// Retry list of the seeds to re-run, one per line
+    #[arg(long, default_value = "retry.txt")]
+    input: PathBuf,
+    /// Only re-run the seeds of shard `i` out of `n`, to split a list between processes
+    #[arg(long, default_value_t = Shard::default())]
+    shard: Shard,
+    /// Budget of the first attempt
+    #[command(flatten)]
+    budget: BudgetArgs,
+    #[command(flatten)]
+    search: SearchArgs,
+    /// Factor the budget grows by after every attempt that times out
+    #[arg(long, default_value_t = 2.0)]
+    factor: f64,
+    /// Largest time limit to try, in seconds
+    #[arg(long, default_value_t = 600.0)]
+    cap_time_limit: f64,
+    /// Largest node budget to try, unlimited if not given
+    #[arg(long)]
+    cap_nodes: Option<u64>,
+    /// Search each deal on this many threads, retrying deals one after the other instead of
+    /// several at once. Suits the few hardest deals of a batch.
+    #[arg(long, default_value_t = 1, conflicts_with_all = ["strategy", "relaxed_check"])]
+    threads: usize,
+    /// JSON Lines file the results are appended to
+    #[arg(long, default_value = "retry_results.jsonl")]
+    output: PathBuf,
+    /// File the seeds of deals still unresolved at the cap are appended to
+    #[arg(long, default_value = "unresolved.txt")]
+    retry_list: PathBuf,
+}
 
-    fn set_first_unlocked_index(&mut self, tableau_idx: usize) {
-        if self.tableaus[tableau_idx].0.is_empty() {
-            self.first_unlocked_idx[tableau_idx] = u8::MAX;
-        } else {
-            self.first_unlocked_idx[tableau_idx] = 0;
-            for (card_idx, _) in self.tableaus[tableau_idx].0.iter().enumerate().rev() {
-                if !self.is_card_unlocked(tableau_idx, card_idx) {
-                    self.first_unlocked_idx[tableau_idx] = card_idx as u8 + 1;
-                    break;
-                }
-            }
-        }
-    }
+#[derive(Args)]
+struct SolveArgs {
+    /// Seed of the deal to solve
+    #[arg(long)]
+    seed: u32,
+    #[command(flatten)]
+    budget: BudgetArgs,
+    #[command(flatten)]
+    search: SearchArgs,
+    /// Print the search stats every this many expanded states
+    #[arg(long, default_value_t = 100_000)]
+    progress_interval: u64,
+    /// Search on this many threads. Progress is only printed by the single-threaded search.
+    #[arg(long, default_value_t = 1, conflicts_with_all = ["strategy", "relaxed_check"])]
+    threads: usize,
+    /// Save the search to this file every `checkpoint_interval` seconds, and when it is
+    /// interrupted or runs out of budget. Only the single-threaded search is saved.
+    #[arg(long, conflicts_with = "threads")]
+    checkpoint: Option<PathBuf>,
+    /// Seconds between two checkpoints
+    #[arg(long, default_value_t = 300.0)]
+    checkpoint_interval: f64,
+    /// Resume the search saved in the checkpoint file, with the moves it was started with.
+    /// The budget covers the whole search, earlier sessions included.
+    #[arg(
+        long,
+        requires = "checkpoint",
+        conflicts_with_all = ["moves", "strategy", "no_deadlock_pruning", "relaxed_check"]
+    )]
+    resume: bool,
+    /// Search breadth-first with the visited states and the frontier in files under this
+    /// directory, to prove deals unsolvable that don't fit in memory
+    #[arg(
+        long,
+        conflicts_with_all = ["threads", "checkpoint", "strategy", "relaxed_check"]
+    )]
+    external: Option<PathBuf>,
+    /// MiB of states the external search holds in memory before spilling them to disk
+    #[arg(long, default_value_t = DEFAULT_MEMORY_LIMIT as f64 / (1024.0 * 1024.0), requires = "external")]
+    memory_limit: f64,
+    /// Keep the visited states in a Bloom filter of this many MiB instead of a hash set.
+    /// Searches more states in the same memory, but an unsolvable result is not a proof.
+    #[arg(long, conflicts_with_all = ["threads", "external", "resume"])]
+    bloom_memory: Option<f64>,
+}
 
-    fn initial_deal(&mut self) {
-        self.tableaus[0]
-            .0
-            .try_extend_from_slice(&[self.stock.0[51]])
-            .expect("Could not extend tableau");
-        self.tableaus[1]
-            .0
-            .try_extend_from_slice(&[self.stock.0[51 - 1].face_down(), self.stock.0[51 - 7]])
-            .expect("Could not  extend tableau");
-        self.tableaus[2]
-            .0
-            .try_extend_from_slice(&[
-                self.stock.0[51 - 2].face_down(),
-                self.stock.0[51 - 8].face_down(),
-                self.stock.0[51 - 13],
-            ])
-            .expect("Could not extend tableau");
-        self.tableaus[3]
-            .0
-            .try_extend_from_slice(&[
-                self.stock.0[51 - 3].face_down(),
-                self.stock.0[51 - 9].face_down(),
-                self.stock.0[51 - 14].face_down(),
-                self.stock.0[51 - 18],
-            ])
-            .expect("Could not extend tableau");
-        self.tableaus[4]
-            .0
-            .try_extend_from_slice(&[
-                self.stock.0[51 - 4].face_down(),
-                self.stock.0[51 - 10].face_down(),
-                self.stock.0[51 - 15].face_down(),
-                self.stock.0[51 - 19].face_down(),
-                self.stock.0[51 - 22],
-            ])
-            .expect("Could not extend tableau");
-        self.tableaus[5]
-            .0
-            .try_extend_from_slice(&[
-                self.stock.0[51 - 5].face_down(),
-                self.stock.0[51 - 11].face_down(),
-                self.stock.0[51 - 16].face_down(),
-                self.stock.0[51 - 20].face_down(),
-                self.stock.0[51 - 23].face_down(),
-                self.stock.0[51 - 25],
-            ])
-            .expect("Could not extend tableau");
-        self.tableaus[6]
-            .0
-            .try_extend_from_slice(&[
-                self.stock.0[51 - 6].face_down(),
-                self.stock.0[51 - 12].face_down(),
-                self.stock.0[51 - 17].face_down(),
-                self.stock.0[51 - 21].face_down(),
-                self.stock.0[51 - 24].face_down(),
-                self.stock.0[51 - 26].face_down(),
-                self.stock.0[51 - 27],
-            ])
-            .expect("Could not extend tableau");
-        for tableau_idx in 0..7 {
-            self.set_first_unlocked_index(tableau_idx);
-        }
-        self.stock.0.truncate(NUM_CARDS_DECK as usize - 28);
-    }
+#[derive(Args)]
+struct ReportArgs {
+    /// Results files to summarize. A later file's result for a seed replaces an earlier one,
+    /// so retry results should come after the batch they retry.
+    #[arg(default_value = "results.jsonl")]
+    inputs: Vec<PathBuf>,
+    /// Normal quantile of the confidence intervals, 1.96 for 95%
+    #[arg(long, default_value_t = 1.96)]
+    z: f64,
+}
 
-    //
-    // Logic Checks
-    //
+#[derive(Args)]
+struct PerftArgs {
+    /// Seed of the deal to count from
+    #[arg(long)]
+    seed: u32,
+    /// Length of the longest move sequences counted
+    #[arg(long, default_value_t = 5)]
+    depth: u32,
+    /// Moves to count, `pruned` or `complete`
+    #[arg(long, default_value_t = MoveGeneration::default())]
+    moves: MoveGeneration,
+}
 
-    fn can_be_placed_on_top_of(bottom: Card, top: Card) -> bool {
-        are_card_ranks_descending(bottom, top) && are_card_colors_different(bottom, top)
-    }
+#[derive(Args)]
+struct PlayArgs {
+    /// Seeds of the deals to play, end excluded
+    #[arg(long, default_value = "0..1")]
+    seeds: SeedRange,
+    /// Search iterations before each move, each running a rollout
+    #[arg(long, default_value_t = DEFAULT_ITERATIONS)]
+    iterations: u64,
+    /// Seconds the search before a move may take, even if iterations are left
+    #[arg(long)]
+    time_per_move: Option<f64>,
+    /// How rollouts pick their moves: `random`, or `heuristic` (closest to a win first)
+    #[arg(long, default_value_t = Rollout::default())]
+    rollout: Rollout,
+    /// How much the search favours rarely tried moves over the best scoring ones
+    #[arg(long, default_value_t = DEFAULT_EXPLORATION)]
+    exploration: f64,
+    /// Moves to play, `pruned` or `complete`
+    #[arg(long, default_value_t = MoveGeneration::default())]
+    moves: MoveGeneration,
+    /// Seed of the rollouts, the same one playing the same games
+    #[arg(long, default_value_t = 0)]
+    random_seed: u32,
+    /// Normal quantile of the confidence interval on the win rate, 1.96 for 95%
+    #[arg(long, default_value_t = 1.96)]
+    z: f64,
+}
 
-    fn can_move_card_to_foundation(&self, card: Card) -> bool {
-        let top_foundation_card = self.foundations[suit_rank(card) as usize];
-        if top_foundation_card != u8::MAX {
-            are_card_ranks_ascending(top_foundation_card, card)
-                && are_card_suits_the_same(top_foundation_card, card)
-        } else {
-            card_rank(card) == 1
-        }
-    }
+fn mib_to_bytes(mib: f64) -> usize {
+    (mib * 1024.0 * 1024.0) as usize
+}
 
-    fn is_card_unlocked(&self, tableau_idx: usize, card_idx: usize) -> bool {
-        if card_idx == self.tableaus[tableau_idx].0.len() - 1 {
-            true
-        } else {
-            let card = self.tableaus[tableau_idx].0[card_idx];
-            let card_above = self.tableaus[tableau_idx].0[card_idx + 1];
-            card.is_face_up() && Self::can_be_placed_on_top_of(card, card_above)
-        }
-    }
+struct EarlyStop {
+    target_width: f64,
+    z: f64,
+}
 
-    fn is_game_won(&self) -> bool {
-        self.foundations
-            .iter()
-            .fold(0, |acc, card| acc + card_rank(*card) as usize)
-            == ranking_of_kings()
-    }
+// A token that gets cancelled on Ctrl-C, so running searches can wind down cleanly
+fn cancel_on_ctrl_c() -> CancellationToken {
+    let cancellation = CancellationToken::new();
+    let handler_cancellation = cancellation.clone();
+    ctrlc::set_handler(move || {
+        warn!("Interrupted, stopping the search");
+        handler_cancellation.cancel();
+    })
+    .expect("could not set Ctrl-C handler");
+    cancellation
+}
 
-    //
-    // Actions
-    //
-    fn handle_move(&self, mv: &Move) -> Self {
-        let Move { from, to } = mv;
-        let mut game = match (from, to) {
-            (CardPosition::Stock, CardPosition::Waste) => self.draw_from_stock(1),
-            (CardPosition::Waste, CardPosition::Stock) => self.restock(),
-            (CardPosition::Waste, CardPosition::Foundation(_)) => {
-                self.move_from_waste_to_foundation()
-            }
-            (CardPosition::Waste, CardPosition::Tableau((tableau_idx, _))) => {
-                self.move_from_waste_to_tableau(*tableau_idx)
-            }
-            (CardPosition::Tableau((tableau_idx, _)), CardPosition::Foundation(_)) => {
-                self.move_from_tableau_to_foundation(*tableau_idx)
+// Solves every seed of the shard not in `completed` in parallel with `solve`, streaming the
+// results to the output files and printing a summary at the end. Deals interrupted by Ctrl-C
+// are not recorded, so running the same command again picks them up.
+fn run_seeds(
+    seeds: impl IntoIterator<Item = u32>,
+    shard: Shard,
+    completed: HashMap<u32, Outcome>,
+    output: &Path,
+    retry_list: &Path,
+    early_stop: Option<EarlyStop>,
+    solve: impl Fn(u32, &CancellationToken) -> SolveResult + Sync,
+) {
+    let (seeds, recorded) = pending_seeds(seeds, shard, &completed);
+    println!(
+        "Solving {} deals (shard {}), {} already recorded",
+        seeds.len(),
+        shard,
+        recorded
+    );
+
+    let writer = JsonLinesWriter::append(output).expect("could not open output file");
+    let retry_writer = JsonLinesWriter::append(retry_list).expect("could not open retry list");
+    let mut all_counts = OutcomeCounts::default();
+    completed
+        .values()
+        .for_each(|outcome| all_counts.add(*outcome));
+    let all_counts = Mutex::new(all_counts);
+    let run_counts = Mutex::new(OutcomeCounts::default());
+    let stopped = AtomicBool::new(false);
+    let cancellation = cancel_on_ctrl_c();
+    seeds.into_par_iter().for_each(|seed| {
+        if stopped.load(Ordering::Relaxed) || cancellation.is_cancelled() {
+            return;
+        }
+        let result = solve(seed, &cancellation);
+        if result.outcome == Outcome::Cancelled {
+            return;
+        }
+        writer.write(&result).expect("could not write result");
+        if result.outcome == Outcome::Timeout {
+            retry_writer
+                .write(&seed)
+                .expect("could not write retry list");
+        }
+        run_counts.lock().unwrap().add(result.outcome);
+        let mut all_counts = all_counts.lock().unwrap();
+        all_counts.add(result.outcome);
+        if let Some(EarlyStop { target_width, z }) = early_stop {
+            if all_counts.solvable_interval_width(z) < target_width {
+                stopped.store(true, Ordering::Relaxed);
             }
-            (
-                CardPosition::Tableau((from_tableau_idx, card_idx)),
-                CardPosition::Tableau((to_tableau_idx, _)),
-            ) => self.move_stack_between_tableaus(*from_tableau_idx, *card_idx, *to_tableau_idx),
-            _ => unreachable!(),
-        };
-        game.prev_move = Some(*mv);
-        if DEBUG {
-            game.validate()
         }
-        game
+    });
+
+    let run_counts = run_counts.into_inner().unwrap();
+    println!(
+        "Solved: {}, Unsolvable: {}, Timeout: {}",
+        run_counts.solved, run_counts.unsolvable, run_counts.unknown
+    );
+    if stopped.into_inner() {
+        println!("Stopped early, the solvable rate interval is below the target width");
     }
+}
 
-    fn restock(&self) -> Self {
-        let mut new_game = self.clone();
-        new_game.waste.0.reverse();
-        std::mem::swap(&mut new_game.stock, &mut new_game.waste);
-        new_game
+fn batch(args: BatchArgs) {
+    let seeds: Box<dyn Iterator<Item = u32>> = match &args.from_list {
+        Some(path) => Box::new(
+            read_seed_list(path)
+                .expect("could not read seed list")
+                .into_iter(),
+        ),
+        None => Box::new(args.seeds.0.clone()),
+    };
+    let mut completed = completed_deals(&args.output).expect("could not read output file");
+    if args.from_list.is_some() {
+        retain_resolved(&mut completed);
     }
+    let budget = args.budget.budget();
+    let early_stop = args.target_width.map(|target_width| EarlyStop {
+        target_width,
+        z: args.z,
+    });
+    run_seeds(
+        seeds,
+        args.shard,
+        completed,
+        &args.output,
+        &args.retry_list,
+        early_stop,
+        |seed, cancellation| {
+            let config = args
+                .search
+                .config(Deal::from_seed(seed), budget, cancellation.clone());
+            let mut solver = args.search.solver(config);
+            if let Some(memory) = args.bloom_memory {
+                solver = solver.with_bloom_filter(mib_to_bytes(memory));
+            }
+            solver.solve()
+        },
+    );
+}
 
-    fn draw_from_stock(&self, count: usize) -> Self {
-        let mut new_game = self.clone();
-        (0..count).for_each(|_| {
-            new_game
-                .waste
-                .0
-                .push(new_game.stock.0.pop().expect("Popped empty stock"));
+fn retry(args: RetryArgs) {
+    let seeds = read_seed_list(&args.input).expect("could not read retry list");
+    let completed = completed_deals(&args.output).expect("could not read output file");
+    let initial = args.budget.budget();
+    let cap = Budget {
+        time_limit_ms: (args.cap_time_limit * 1000.0) as u64,
+        max_nodes: args.cap_nodes,
+    };
+    let solve = |seed, cancellation: &CancellationToken| {
+        solve_with_escalation(initial, args.factor, cap, |budget| {
+            let config = args
+                .search
+                .config(Deal::from_seed(seed), budget, cancellation.clone());
+            if args.threads > 1 {
+                ParallelSolver::new(config)
+                    .with_threads(args.threads)
+                    .solve()
+            } else {
+                args.search.solver(config).solve()
+            }
+        })
+    };
+    let pool_threads = if args.threads > 1 { 1 } else { 0 };
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(pool_threads)
+        .build()
+        .expect("could not start thread pool")
+        .install(|| {
+            run_seeds(
+                seeds,
+                args.shard,
+                completed,
+                &args.output,
+                &args.retry_list,
+                None,
+                solve,
+            )
         });
-        new_game
-    }
-
-    fn move_from_waste_to_foundation(&self) -> Self {
-        let mut new_game = self.clone();
-        let card = new_game.waste.0.pop().expect("Popped empty waste");
-        new_game.foundations[suit_rank(card) as usize] = card;
-        new_game.foundation_stack |= 1 << card;
-        new_game
-    }
-
-    fn move_from_tableau_to_foundation(&self, tableau_idx: u8) -> Self {
-        let mut new_game = self.clone();
-        let card = new_game.tableaus[tableau_idx as usize]
-            .0
-            .pop()
-            .expect("Popped empty tableau");
-        new_game.tableaus[tableau_idx as usize].flip_face_up();
-        new_game.foundations[suit_rank(card) as usize] = card;
-
-        new_game.set_first_unlocked_index(tableau_idx as usize);
+}
 
-        new_game.foundation_stack |= 1 << card;
-        new_game
+fn solve(args: SolveArgs) {
+    // Which strategies can be saved depends on the value, so clap can't check it
+    if args.checkpoint.is_some() && !args.search.strategy.is_resumable() {
+        let err = CheckpointError::UnsupportedStrategy(args.search.strategy);
+        Cli::command()
+            .error(ErrorKind::ArgumentConflict, err)
+            .exit();
     }
-
-    fn move_from_waste_to_tableau(&self, tableau_idx: u8) -> Self {
-        let mut new_game = self.clone();
-        new_game.tableaus[tableau_idx as usize]
-            .0
-            .push(new_game.waste.0.pop().expect("Popped empty waste"));
-
-        if new_game.tableaus[tableau_idx as usize].0.len() == 1 {
-            new_game.set_first_unlocked_index(tableau_idx as usize);
+    let budget = args.budget.budget();
+    let cancellation = cancel_on_ctrl_c();
+    let config = args
+        .search
+        .config(Deal::from_seed(args.seed), budget, cancellation.clone());
+    let result = if let Some(dir) = &args.external {
+        ExternalSolver::new(config, dir)
+            .with_memory_limit(mib_to_bytes(args.memory_limit))
+            .solve()
+    } else if args.threads > 1 {
+        ParallelSolver::new(config)
+            .with_threads(args.threads)
+            .solve()
+    } else {
+        let mut solver = match &args.checkpoint {
+            Some(path) if args.resume => {
+                let solver = Solver::from_checkpoint(path, budget, Some(cancellation))
+                    .unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+                if solver.config().deal().seed != args.seed {
+                    panic!(
+                        "{} is a search of seed {}, not {}",
+                        path.display(),
+                        solver.config().deal().seed,
+                        args.seed
+                    );
+                }
+                solver
+            }
+            _ => args.search.solver(config),
+        };
+        if let Some(memory) = args.bloom_memory {
+            solver = solver.with_bloom_filter(mib_to_bytes(memory));
         }
-        new_game
-    }
-
-    fn move_stack_between_tableaus(&self, from_index: u8, card_idx: u8, to_index: u8) -> Self {
-        let mut new_game = self.clone();
-        let drain_iter = new_game.tableaus[from_index as usize]
-            .0
-            .drain((card_idx as usize)..)
-            .collect::<Vec<_>>();
-
-        // TODO: You are the problem!
-        new_game.tableaus[to_index as usize].0.extend(drain_iter);
-        new_game.tableaus[from_index as usize]
-            .0
-            .truncate(card_idx as usize);
-        new_game.tableaus[from_index as usize].flip_face_up();
+        if let Some(path) = &args.checkpoint {
+            let interval = Duration::from_secs_f64(args.checkpoint_interval);
+            solver = solver.with_checkpoint(path, interval);
+        }
+        solver
+            .with_progress(args.progress_interval, |stats: &SearchStats| {
+                eprintln!(
+                    "Expanded: {}, Duplicates: {}, Dead Ends: {}, Deadlocks: {}, Max Depth: {}, Frontier: {}, Elapsed: {}ms",
+                    stats.expanded_nodes,
+                    stats.duplicates,
+                    stats.dead_ends,
+                    stats.deadlocks,
+                    stats.max_depth,
+                    stats.peak_frontier_size,
+                    stats.elapsed_ms
+                )
+            })
+            .solve()
+    };
+    println!(
+        "{}",
+        serde_json::to_string(&result).expect("could not serialize result")
+    );
+}
 
-        new_game.set_first_unlocked_index(from_index as usize);
-        new_game.set_first_unlocked_index(to_index as usize);
+fn report(args: ReportArgs) {
+    let results = args
+        .inputs
+        .iter()
+        .flat_map(|path| {
+            read_results(path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err))
+        })
+        .collect::<Vec<_>>();
+    print!("{}", Report::new(&results, args.z));
+}
 
-        new_game
+fn perft(args: PerftArgs) {
+    let game = Game::from_deal(&Deal::from_seed(args.seed), Rules::default());
+    let counts = solitaire_solvability::perft::perft(&game, args.depth, args.moves)
+        .unwrap_or_else(|err| panic!("seed {}: {}", args.seed, err));
+    for depth_counts in counts {
+        println!("{}", depth_counts);
     }
 }
 
-impl fmt::Display for Game {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(
-            f,
-            "================== SEED: {:?} ==================",
-            self.random_seed
-        )?;
-        writeln!(f, "--------- Foundations ---------")?;
-        self.foundations
-            .iter()
-            .try_for_each(|card| write!(f, "[{}]\t", pretty_string(*card)))?;
-        writeln!(f)?;
-        writeln!(f, "--------- Tableaus ------------")?;
-        self.tableaus
-            .iter()
-            .enumerate()
-            .try_for_each(|(idx, tableau)| {
-                write!(f, "{}:\t", idx)?;
-                tableau
-                    .0
-                    .iter()
-                    .try_for_each(|card| write!(f, "{}\t", pretty_string(*card)))?;
-                writeln!(f)
-            })?;
-        writeln!(f, "--------- Unlocked ------------")?;
-        self.first_unlocked_idx
-            .iter()
-            .try_for_each(|idx| write!(f, "{:?}\t", idx))?;
-        writeln!(f)?;
-        writeln!(f, "--------- Stock ---------------")?;
-        self.stock
-            .0
-            .iter()
-            .try_for_each(|card| write!(f, "{} ", pretty_string(*card)))?;
-        writeln!(f)?;
-        writeln!(f, "--------- Waste ---------------")?;
-        self.waste
-            .0
-            .iter()
-            .try_for_each(|card| write!(f, "{} ", pretty_string(*card)))?;
-        writeln!(f)?;
-        if VERBOSE_PRINT {
-            writeln!(f, "--------- Valid Moves ---------")?;
-            self.valid_moves()
-                .iter()
-                .try_for_each(|mv| writeln!(f, "{}", mv.pretty_string(self)))?;
+fn play(args: PlayArgs) {
+    let mut won = 0;
+    for seed in args.seeds.0.clone() {
+        let mut player = MctsPlayer::from_deal(Deal::from_seed(seed), Rules::default())
+            .with_iterations(args.iterations)
+            .with_rollout(args.rollout)
+            .with_exploration(args.exploration)
+            .with_move_generation(args.moves)
+            .with_seed(args.random_seed);
+        if let Some(time) = args.time_per_move {
+            player = player.with_time_per_move(Duration::from_secs_f64(time));
         }
-        writeln!(f, "--------- Prev Move -----------")?;
-        writeln!(f, "{:?}", self.prev_move)
+        let result = player
+            .play()
+            .unwrap_or_else(|err| panic!("seed {}: {}", seed, err));
+        won += result.won as usize;
+        println!(
+            "{}",
+            serde_json::to_string(&result).expect("could not serialize result")
+        );
     }
-}
-
-#[derive(Serialize)]
-struct SolvableGames {
-    solvers: Vec<Solver>,
+    let played = args.seeds.0.len();
+    let (low, high) = wilson_interval(won, played, args.z);
+    eprintln!(
+        "Won {} of {} deals, {:.3}%  [{:.3}%, {:.3}%]",
+        won,
+        played,
+        100.0 * won as f64 / played.max(1) as f64,
+        100.0 * low,
+        100.0 * high
+    );
 }
 
 fn main() {
-    let num_iters = 10000;
-    let solvers = (0..num_iters)
-        .into_par_iter()
-        .map(|_| Solver::new().is_solvable())
-        .filter_map(|solver| solver)
-        .collect::<Vec<_>>();
-
-    static PRINT_ORIGINAL_STATE: bool = false;
-    if PRINT_ORIGINAL_STATE {
-        for solver in &solvers {
-            solver.log_original_state()
-        }
+    let cli = Cli::parse();
+    let level = match cli.verbose {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .init();
+
+    match cli.command {
+        Command::Batch(args) => batch(args),
+        Command::Retry(args) => retry(args),
+        Command::Solve(args) => solve(args),
+        Command::Report(args) => report(args),
+        Command::Perft(args) => perft(args),
+        Command::Play(args) => play(args),
     }
-
-    let stocks = solvers
-        .iter()
-        .map(|solver| solver.original_stock())
-        .collect::<Vec<_>>();
-    std::fs::write("compact_solvable_games.txt", format!("{:?}", stocks))
-        .expect("could not write json file");
-
-    let solvable_games = SolvableGames { solvers };
-    let json_string =
-        serde_json::to_string_pretty(&solvable_games).expect("could not create json string");
-    std::fs::write("solvable_games.json", &json_string).expect("could not write json file");
 }
-
-// TODO: Reduce symmetry in suit permutation
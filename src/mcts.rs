@@ -0,0 +1,434 @@
+use log::{debug, info};
+use rand::{Rng, SeedableRng, XorShiftRng};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    card::NUM_CARDS_DECK,
+    deal::Deal,
+    moves::{Move, MoveError, MoveGeneration},
+    rules::Rules,
+    solver::GameCompact,
+    strategy::distance_estimate,
+    Game,
+};
+
+// Bump whenever the serialized layout of `PlayResult` changes
+pub const PLAY_SCHEMA_VERSION: u32 = 1;
+
+pub const DEFAULT_ITERATIONS: u64 = 1000;
+
+// UCT exploration constant, the usual one for rewards between 0 and 1
+pub const DEFAULT_EXPLORATION: f64 = std::f64::consts::SQRT_2;
+
+// Moves a rollout plays before the state it reached is scored as it stands
+const MAX_ROLLOUT_MOVES: usize = 200;
+
+// Moves a game may last before it is given up as lost
+const MAX_GAME_MOVES: usize = 1000;
+
+// Chance that a heuristic rollout plays a random move instead of the most promising one, so
+// that rollouts from the same state don't all play the same game
+const HEURISTIC_RANDOMNESS: f64 = 0.1;
+
+// How a rollout picks its moves from the state the tree search expanded down to
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rollout {
+    // Uniformly among the moves
+    #[default]
+    Random,
+    // The move to the state closest to a win by `distance_estimate`, with some randomness
+    Heuristic,
+}
+
+impl FromStr for Rollout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Rollout::Random),
+            "heuristic" => Ok(Rollout::Heuristic),
+            _ => Err(format!(
+                "`{}` is not a rollout, expected `random` or `heuristic`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Rollout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rollout::Random => write!(f, "random"),
+            Rollout::Heuristic => write!(f, "heuristic"),
+        }
+    }
+}
+
+// A game played to the end by `MctsPlayer`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayResult {
+    pub version: u32,
+    pub deal: Deal,
+    pub rules: Rules,
+    pub rollout: Rollout,
+    // The search iterations allowed before each move
+    pub iterations_per_move: u64,
+    pub won: bool,
+    // Cards on the foundations when the game ended, all of them if it was won
+    pub foundation_cards: u32,
+    pub moves: Vec<Move>,
+    // Search iterations run over the whole game
+    pub iterations: u64,
+    pub elapsed_ms: u64,
+}
+
+impl PlayResult {
+    // Rebuilds the game as it was dealt, before any move was played
+    pub fn game(&self) -> Game {
+        Game::from_deal(&self.deal, self.rules)
+    }
+
+    // Plays the moves from the start of the deal, returning the final state
+    pub fn replay(&self) -> Result<Game, MoveError> {
+        self.moves
+            .iter()
+            .try_fold(self.game(), |game, mv| game.play_move(mv))
+    }
+}
+
+// Share of the cards on the foundations, 1 for a won game. Scoring lost games by how far
+// they got gives the search something to go on long before any rollout wins.
+fn reward(game: &Game) -> f64 {
+    game.foundation_stack.count_ones() as f64 / NUM_CARDS_DECK as f64
+}
+
+// XorShift needs a seed that isn't all zeros
+fn seeded_rng(seed: u32, deal: &Deal) -> XorShiftRng {
+    XorShiftRng::from_seed([seed, deal.seed, 0x9e37_79b9, 1])
+}
+
+struct TreeNode {
+    game: Game,
+    // The move from the parent, `None` at the root
+    mv: Option<Move>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    // Moves not expanded into a child yet, in a random order
+    untried: Vec<Move>,
+    visits: u32,
+    total_reward: f64,
+}
+
+// Plays a deal with Monte Carlo tree search: before every move it grows a search tree from
+// the current state, scoring each new leaf with a rollout to the end of the game, and plays
+// the move it visited most. Unlike `Solver` it never proves anything, but it plays on deals
+// far too large to search exhaustively.
+//
+// States are never played twice, neither in the game nor within a rollout, and the tree holds
+// each state once, so that cycling through the stock or moving a king back and forth
+// between empty tableaus doesn't go on forever.
+pub struct MctsPlayer {
+    deal: Deal,
+    original_game: Game,
+    iterations: u64,
+    time_per_move: Option<Duration>,
+    rollout: Rollout,
+    exploration: f64,
+    move_generation: MoveGeneration,
+    rng: XorShiftRng,
+}
+
+impl MctsPlayer {
+    pub fn from_deal(deal: Deal, rules: Rules) -> Self {
+        let original_game = Game::from_deal(&deal, rules);
+        let rng = seeded_rng(0, &deal);
+        Self {
+            deal,
+            original_game,
+            iterations: DEFAULT_ITERATIONS,
+            time_per_move: None,
+            rollout: Rollout::default(),
+            exploration: DEFAULT_EXPLORATION,
+            move_generation: MoveGeneration::default(),
+            rng,
+        }
+    }
+
+    // Search iterations before each move, each adding a state to the tree and running a
+    // rollout from it
+    pub fn with_iterations(mut self, iterations: u64) -> Self {
+        self.iterations = iterations.max(1);
+        self
+    }
+
+    // Stops the search before a move once this much time is spent, even if iterations are left
+    pub fn with_time_per_move(mut self, time: Duration) -> Self {
+        self.time_per_move = Some(time);
+        self
+    }
+
+    pub fn with_rollout(mut self, rollout: Rollout) -> Self {
+        self.rollout = rollout;
+        self
+    }
+
+    // How much the search favours rarely visited moves over the best scoring ones
+    pub fn with_exploration(mut self, exploration: f64) -> Self {
+        self.exploration = exploration;
+        self
+    }
+
+    pub fn with_move_generation(mut self, generation: MoveGeneration) -> Self {
+        self.move_generation = generation;
+        self
+    }
+
+    // Seeds the rollouts along with the deal, so that the same deal, seed and settings play
+    // the same game
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.rng = seeded_rng(seed, &self.deal);
+        self
+    }
+
+    // The legal moves from `game` to a state not in `seen`, with those states, sorted so that
+    // the game only depends on the random seed
+    fn next_moves(
+        &self,
+        game: &Game,
+        seen: &HashSet<GameCompact>,
+    ) -> Result<Vec<(Move, Game)>, MoveError> {
+        let mut moves = game
+            .moves(self.move_generation)
+            .into_iter()
+            .collect::<Vec<_>>();
+        moves.sort_unstable();
+        let mut next_moves = Vec::with_capacity(moves.len());
+        for mv in moves {
+            let next_game = game.handle_move(&mv)?;
+            if !seen.contains(&next_game.compact_state()) {
+                next_moves.push((mv, next_game));
+            }
+        }
+        Ok(next_moves)
+    }
+
+    fn new_node(
+        &mut self,
+        game: Game,
+        mv: Option<Move>,
+        parent: Option<usize>,
+        seen: &HashSet<GameCompact>,
+    ) -> Result<TreeNode, MoveError> {
+        let mut untried = if game.is_game_won() {
+            Vec::new()
+        } else {
+            let next_moves = self.next_moves(&game, seen)?;
+            next_moves.into_iter().map(|(mv, _)| mv).collect()
+        };
+        self.rng.shuffle(&mut untried);
+        Ok(TreeNode {
+            game,
+            mv,
+            parent,
+            children: Vec::new(),
+            untried,
+            visits: 0,
+            total_reward: 0.0,
+        })
+    }
+
+    // The child of `node` with the highest upper confidence bound
+    fn select_child(&self, tree: &[TreeNode], node: usize) -> usize {
+        let log_visits = (tree[node].visits as f64).ln();
+        let bound = |child: usize| {
+            let child = &tree[child];
+            let visits = child.visits as f64;
+            child.total_reward / visits + self.exploration * (log_visits / visits).sqrt()
+        };
+        tree[node]
+            .children
+            .iter()
+            .copied()
+            .max_by(|a, b| bound(*a).total_cmp(&bound(*b)))
+            .unwrap()
+    }
+
+    // Plays from `game` to the end or to `MAX_ROLLOUT_MOVES`, avoiding the states in `seen`
+    fn rollout(&mut self, game: &Game, seen: &HashSet<GameCompact>) -> Result<f64, MoveError> {
+        let mut game = game.clone();
+        let mut rollout_seen = HashSet::new();
+        for _ in 0..MAX_ROLLOUT_MOVES {
+            if game.is_game_won() {
+                break;
+            }
+            let mut moves = game
+                .moves(self.move_generation)
+                .into_iter()
+                .collect::<Vec<_>>();
+            moves.sort_unstable();
+            let is_new = |next_game: &Game| {
+                let state = next_game.compact_state();
+                !seen.contains(&state) && !rollout_seen.contains(&state)
+            };
+            let random = match self.rollout {
+                Rollout::Random => true,
+                Rollout::Heuristic => self.rng.gen::<f64>() < HEURISTIC_RANDOMNESS,
+            };
+            let mut next = None;
+            if random {
+                // Playing the moves in a random order until one leads to a new state is much
+                // cheaper than playing them all to pick one
+                self.rng.shuffle(&mut moves);
+                for mv in moves {
+                    let next_game = game.handle_move(&mv)?;
+                    if is_new(&next_game) {
+                        next = Some(next_game);
+                        break;
+                    }
+                }
+            } else {
+                let mut best = None;
+                for mv in moves {
+                    let next_game = game.handle_move(&mv)?;
+                    let estimate = distance_estimate(&next_game);
+                    if is_new(&next_game) && best.is_none_or(|best| estimate < best) {
+                        best = Some(estimate);
+                        next = Some(next_game);
+                    }
+                }
+            }
+            let Some(next_game) = next else {
+                break;
+            };
+            rollout_seen.insert(next_game.compact_state());
+            game = next_game;
+        }
+        Ok(reward(&game))
+    }
+
+    // Searches from `game` and returns the move to play with the state it leads to, `None`
+    // if every move leads back to a state already played
+    fn choose_move(
+        &mut self,
+        game: &Game,
+        played: &HashSet<GameCompact>,
+        iterations: &mut u64,
+    ) -> Result<Option<(Move, Game)>, MoveError> {
+        let mut next_moves = self.next_moves(game, played)?;
+        if next_moves.len() <= 1 {
+            return Ok(next_moves.pop());
+        }
+        // A won state ends the game at once, whatever the search would make of it
+        if let Some(won) = next_moves.iter().position(|(_, next)| next.is_game_won()) {
+            return Ok(Some(next_moves.swap_remove(won)));
+        }
+
+        let timer = Instant::now();
+        let mut seen = played.clone();
+        let mut tree = vec![self.new_node(game.clone(), None, None, &seen)?];
+        for iteration in 0..self.iterations {
+            // At least one iteration, so that even a zero time budget picks a move
+            if iteration > 0
+                && self
+                    .time_per_move
+                    .is_some_and(|time| timer.elapsed() >= time)
+            {
+                break;
+            }
+            *iterations += 1;
+
+            // Selection, down to a node with moves left to expand or to the end of the game
+            let mut node = 0;
+            while tree[node].untried.is_empty() && !tree[node].children.is_empty() {
+                node = self.select_child(&tree, node);
+            }
+
+            // Expansion, skipping moves to states added to the tree since the node was
+            while let Some(mv) = tree[node].untried.pop() {
+                let next_game = tree[node].game.handle_move(&mv)?;
+                if seen.insert(next_game.compact_state()) {
+                    let child = self.new_node(next_game, Some(mv), Some(node), &seen)?;
+                    tree.push(child);
+                    let child = tree.len() - 1;
+                    tree[node].children.push(child);
+                    node = child;
+                    break;
+                }
+            }
+
+            let reward = self.rollout(&tree[node].game, &seen)?;
+
+            let mut backup = Some(node);
+            while let Some(node) = backup {
+                tree[node].visits += 1;
+                tree[node].total_reward += reward;
+                backup = tree[node].parent;
+            }
+        }
+
+        // The most visited move is the one the search trusts most
+        let best = tree[0].children.iter().copied().max_by(|a, b| {
+            let score = |node: usize| {
+                let node = &tree[node];
+                (node.visits, node.total_reward / node.visits as f64)
+            };
+            let (a, b) = (score(*a), score(*b));
+            a.0.cmp(&b.0).then(a.1.total_cmp(&b.1))
+        });
+        // Without any iteration, the first move is as good as any
+        Ok(Some(match best {
+            Some(best) => {
+                let best = tree.swap_remove(best);
+                (best.mv.unwrap(), best.game)
+            }
+            None => next_moves.swap_remove(0),
+        }))
+    }
+
+    // Plays the deal until it is won, no move is left or `MAX_GAME_MOVES` are played
+    pub fn play(mut self) -> Result<PlayResult, MoveError> {
+        let timer = Instant::now();
+        let mut game = self.original_game.clone();
+        let mut played = HashSet::from([game.compact_state()]);
+        let mut moves = Vec::new();
+        let mut iterations = 0;
+        while !game.is_game_won() && moves.len() < MAX_GAME_MOVES {
+            let Some((mv, next_game)) = self.choose_move(&game, &played, &mut iterations)? else {
+                break;
+            };
+            debug!("Seed {}: move {} {}", self.deal.seed, moves.len() + 1, mv);
+            played.insert(next_game.compact_state());
+            moves.push(mv);
+            game = next_game;
+        }
+        let won = game.is_game_won();
+        let elapsed_ms = timer.elapsed().as_millis() as u64;
+        info!(
+            "Seed {}: {} after {} moves and {} iterations in {}ms",
+            self.deal.seed,
+            if won { "won" } else { "lost" },
+            moves.len(),
+            iterations,
+            elapsed_ms
+        );
+        Ok(PlayResult {
+            version: PLAY_SCHEMA_VERSION,
+            rules: self.original_game.rules,
+            rollout: self.rollout,
+            iterations_per_move: self.iterations,
+            won,
+            foundation_cards: game.foundation_stack.count_ones(),
+            moves,
+            iterations,
+            elapsed_ms,
+            deal: self.deal,
+        })
+    }
+}
@@ -1,9 +1,21 @@
-use serde::Serialize;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::{card::*, Game};
-use std::collections::HashSet;
+use crate::{card::*, validate::InvalidState, Game};
+use std::{collections::HashSet, fmt, str::FromStr};
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize)]
+// Positions and moves have a compact text notation, used both for display and for serde:
+//   S, W            stock and waste
+//   F<n>            foundation n
+//   T<t>:<c>        card c of tableau t
+// A move is written `<from>-<to>`, leaving out the indices `Game::handle_move` doesn't need:
+// the card index of the destination tableau, the foundation, and the card index of a tableau
+// card going to a foundation. So `W-T3`, `T2:4-T6`, `T5-F` or `W-S` for a restock. Drawing
+// from the stock is abbreviated to `S`.
+//
+// The moves `Game::moves` generates have those indices set to 0, so they are written and
+// parsed back without loss. Parsing also takes the positions in full, like `T5:2-F1`, and sets
+// the indices to 0 all the same, see `Move::normalized` and `Game::resolve_move`.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum CardPosition {
     Stock,
     Waste,
@@ -12,38 +24,253 @@ pub enum CardPosition {
     Tableau((u8, u8)),
 }
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Move {
     pub from: CardPosition,
     pub to: CardPosition,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseNotationError(String);
+
+impl fmt::Display for ParseNotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid move notation: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseNotationError {}
+
+// Why `Game::handle_move` could not play a move
+#[derive(Debug)]
+pub enum MoveError {
+    // The move can't be played in this state, see `Game::play_move`
+    IllegalMove(Move),
+    // The move would grow a tableau past the capacity the rules allow
+    TableauOverflow {
+        tableau: u8,
+        len: usize,
+        capacity: usize,
+    },
+    // The move left the game in a state that breaks an invariant. Only checked when debugging.
+    InvalidState(InvalidState),
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::IllegalMove(mv) => write!(f, "{} is not a legal move", mv),
+            MoveError::TableauOverflow {
+                tableau,
+                len,
+                capacity,
+            } => write!(
+                f,
+                "tableau {} would hold {} cards, more than its capacity of {}",
+                tableau, len, capacity
+            ),
+            MoveError::InvalidState(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+impl From<InvalidState> for MoveError {
+    fn from(err: InvalidState) -> Self {
+        MoveError::InvalidState(err)
+    }
+}
+
+impl fmt::Display for CardPosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CardPosition::Stock => write!(f, "S"),
+            CardPosition::Waste => write!(f, "W"),
+            CardPosition::Foundation(idx) => write!(f, "F{}", idx),
+            CardPosition::Tableau((tableau_idx, card_idx)) => {
+                write!(f, "T{}:{}", tableau_idx, card_idx)
+            }
+        }
+    }
+}
+
+impl FromStr for CardPosition {
+    type Err = ParseNotationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseNotationError(format!("`{}` is not a card position", s));
+        let parse_index = |idx: &str, max: u8| {
+            idx.parse::<u8>()
+                .ok()
+                .filter(|idx| *idx < max)
+                .ok_or_else(err)
+        };
+        let mut chars = s.chars();
+        match (chars.next(), chars.as_str()) {
+            (Some('S'), "") => Ok(CardPosition::Stock),
+            (Some('W'), "") => Ok(CardPosition::Waste),
+            (Some('F'), "") => Ok(CardPosition::Foundation(0)),
+            (Some('F'), idx) => Ok(CardPosition::Foundation(parse_index(idx, 4)?)),
+            (Some('T'), rest) => {
+                let (tableau_idx, card_idx) = rest.split_once(':').unwrap_or((rest, "0"));
+                Ok(CardPosition::Tableau((
+                    parse_index(tableau_idx, 7)?,
+                    parse_index(card_idx, u8::MAX)?,
+                )))
+            }
+            _ => Err(err()),
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.from, self.to) {
+            (CardPosition::Stock, CardPosition::Waste) => return write!(f, "S"),
+            (CardPosition::Tableau((tableau_idx, _)), CardPosition::Foundation(_)) => {
+                write!(f, "T{}", tableau_idx)?
+            }
+            (from, _) => write!(f, "{}", from)?,
+        }
+        match self.to {
+            CardPosition::Foundation(_) => write!(f, "-F"),
+            CardPosition::Tableau((tableau_idx, _)) => write!(f, "-T{}", tableau_idx),
+            to => write!(f, "-{}", to),
+        }
+    }
+}
+
+impl FromStr for Move {
+    type Err = ParseNotationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "S" {
+            return Ok(Move {
+                from: CardPosition::Stock,
+                to: CardPosition::Waste,
+            });
+        }
+        let err = || ParseNotationError(format!("`{}` is not a move", s));
+        let (from, to) = s.split_once('-').ok_or_else(err)?;
+        let mv = Move {
+            from: from.parse()?,
+            to: to.parse()?,
+        }
+        .normalized();
+        // A stack can start anywhere in its tableau, so only a move to a foundation can leave
+        // out which card it takes
+        match (mv.from, mv.to) {
+            (CardPosition::Tableau(_), CardPosition::Tableau(_)) if !from.contains(':') => {
+                Err(err())
+            }
+            _ => Ok(mv),
+        }
+    }
+}
+
+macro_rules! serde_via_notation {
+    ($ty:ty) => {
+        impl Serialize for $ty {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                String::deserialize(deserializer)?
+                    .parse()
+                    .map_err(de::Error::custom)
+            }
+        }
+    };
+}
+
+serde_via_notation!(CardPosition);
+serde_via_notation!(Move);
+
 impl Move {
+    // The move with the indices `Game::handle_move` ignores set to 0, as the short notation
+    // parses them. Two moves from the same state play the same if they normalize the same.
+    pub fn normalized(&self) -> Move {
+        let to = match self.to {
+            CardPosition::Foundation(_) => CardPosition::Foundation(0),
+            CardPosition::Tableau((tableau_idx, _)) => CardPosition::Tableau((tableau_idx, 0)),
+            to => to,
+        };
+        let from = match (self.from, to) {
+            (CardPosition::Tableau((tableau_idx, _)), CardPosition::Foundation(_)) => {
+                CardPosition::Tableau((tableau_idx, 0))
+            }
+            (from, _) => from,
+        };
+        Move { from, to }
+    }
+
     pub fn pretty_string(&self, game: &Game) -> String {
-        let from_card = match self.from {
-            CardPosition::Stock => game.stock.0.last().unwrap(),
-            CardPosition::Waste => game.waste.0.last().unwrap(),
-            CardPosition::Foundation(idx) => &game.foundations[idx as usize],
-            CardPosition::Tableau((tableau_idx, card_idx)) => {
+        let from_card = match (self.from, self.to) {
+            (CardPosition::Stock, _) => game.stock.0.last().unwrap(),
+            (CardPosition::Waste, _) => game.waste.0.last().unwrap(),
+            (CardPosition::Foundation(idx), _) => &game.foundations[idx as usize],
+            (CardPosition::Tableau((tableau_idx, _)), CardPosition::Foundation(_)) => {
+                game.tableaus[tableau_idx as usize].0.last().unwrap()
+            }
+            (CardPosition::Tableau((tableau_idx, card_idx)), _) => {
                 &game.tableaus[tableau_idx as usize].0[card_idx as usize]
             }
         };
         let to_card = match self.to {
             CardPosition::Stock => game.stock.0.last(),
             CardPosition::Waste => game.waste.0.last(),
-            CardPosition::Foundation(idx) => Some(&game.foundations[idx as usize]),
+            CardPosition::Foundation(_) => Some(&game.foundations[suit_rank(*from_card) as usize]),
             CardPosition::Tableau((tableau_idx, _)) => game.tableaus[tableau_idx as usize].0.last(),
         };
         format!(
-            "From: {:?} - {}\tTo: {:?} - {}",
-            self.from,
+            "{}\t{} -> {}",
+            self,
             pretty_string(*from_card),
-            self.to,
             to_card.map_or_else(|| " ".to_string(), |card| pretty_string(*card))
         )
     }
 }
 
+// Which moves `Game::moves` generates
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveGeneration {
+    // Only moves part of a tableau stack when that uncovers a card that can go to a
+    // foundation. The search is much smaller, but it may cut off every winning line of a deal.
+    #[default]
+    Pruned,
+    // Every legal move
+    Complete,
+}
+
+impl FromStr for MoveGeneration {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pruned" => Ok(MoveGeneration::Pruned),
+            "complete" => Ok(MoveGeneration::Complete),
+            _ => Err(format!(
+                "`{}` is not a move generation, expected `pruned` or `complete`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for MoveGeneration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveGeneration::Pruned => write!(f, "pruned"),
+            MoveGeneration::Complete => write!(f, "complete"),
+        }
+    }
+}
+
 impl Game {
     fn get_move_from_stock(&self) -> Option<Move> {
         // If stock is not empty we can draw, otherwise we can restock
@@ -67,22 +294,20 @@ impl Game {
         if let Some(card) = self.waste.0.last() {
             if let Some(tableau_card) = self.tableaus[tableau_idx].0.last() {
                 // If tableau is not empty, only move is waste card can be placed on tableau
-                if Self::can_be_placed_on_top_of(*tableau_card, *card) {
+                if self.can_be_placed_on_top_of(*tableau_card, *card) {
                     Some(Move {
                         from: CardPosition::Waste,
-                        to: CardPosition::Tableau((
-                            tableau_idx as u8,
-                            self.tableaus[tableau_idx].0.len() as u8,
-                        )),
+                        to: CardPosition::Tableau((tableau_idx as u8, 0)),
                     })
                 } else {
                     None
                 }
-            } else if is_king(*card) {
-                // If tableau is empty, only kings can be moved there
+            } else if self.can_go_to_empty_tableau(*card) {
+                // If tableau is empty, only kings can be moved there, unless the rules are
+                // relaxed
                 Some(Move {
                     from: CardPosition::Waste,
-                    to: CardPosition::Tableau((tableau_idx as u8, 0 as u8)),
+                    to: CardPosition::Tableau((tableau_idx as u8, 0)),
                 })
             } else {
                 None
@@ -101,7 +326,7 @@ impl Game {
             if self.can_move_card_to_foundation(*card) {
                 set.insert(Move {
                     from: CardPosition::Waste,
-                    to: CardPosition::Foundation(suit_rank(*card)),
+                    to: CardPosition::Foundation(0),
                 });
             }
 
@@ -127,71 +352,63 @@ impl Game {
         let card = self.tableaus[from_tableau_idx].0[card_idx];
         // Check if to tableau is empty
         if let Some(to_tableau_card) = self.tableaus[to_tableau_idx].0.last() {
-            if Self::can_be_placed_on_top_of(*to_tableau_card, card) {
+            if self.can_be_placed_on_top_of(*to_tableau_card, card) {
                 // If the card we are moving can be placed on top of the top card in to tableau,
                 // Add move to set
                 Some(Move {
                     from: CardPosition::Tableau((from_tableau_idx as u8, card_idx as u8)),
-                    to: CardPosition::Tableau((
-                        to_tableau_idx as u8,
-                        self.tableaus[to_tableau_idx].0.len() as u8,
-                    )),
+                    to: CardPosition::Tableau((to_tableau_idx as u8, 0)),
                 })
             } else {
                 None
             }
-        } else if is_king(card) {
-            // If to tableau is empty, the only card we can move there is a king
+        } else if self.can_go_to_empty_tableau(card) {
+            // If to tableau is empty, the only card we can move there is a king, unless the
+            // rules are relaxed
             Some(Move {
                 from: CardPosition::Tableau((from_tableau_idx as u8, card_idx as u8)),
-                to: CardPosition::Tableau((to_tableau_idx as u8, 0 as u8)),
+                to: CardPosition::Tableau((to_tableau_idx as u8, 0)),
             })
         } else {
             None
         }
     }
 
-    fn get_tableau_moves_from_tableau(&self, from_tableau_idx: usize) -> HashSet<Move> {
+    fn get_tableau_moves_from_tableau(
+        &self,
+        from_tableau_idx: usize,
+        generation: MoveGeneration,
+    ) -> HashSet<Move> {
         let mut set = HashSet::new();
         // Check first unlocked card
         let first_unlocked_idx = self.first_unlocked_idx[from_tableau_idx];
         // If it exists
         if first_unlocked_idx != u8::MAX {
-            // Iterate trough every tableau
-            self.tableaus
-                .iter()
-                .enumerate()
-                .for_each(|(to_tableau_idx, _)| {
+            let first_unlocked_idx = first_unlocked_idx as usize;
+            for index in first_unlocked_idx..self.tableaus[from_tableau_idx].0.len() {
+                // The whole stack can always move. When pruning, the rest of the stack only
+                // moves if it opens a card that can move to a foundation.
+                let opens_foundation_move = || {
+                    self.can_move_card_to_foundation(self.tableaus[from_tableau_idx].0[index - 1])
+                };
+                if index != first_unlocked_idx
+                    && generation == MoveGeneration::Pruned
+                    && !opens_foundation_move()
+                {
+                    continue;
+                }
+                // Iterate trough every tableau
+                for to_tableau_idx in 0..self.tableaus.len() {
                     if from_tableau_idx != to_tableau_idx {
                         // If the tableaus are different, and I can move the stack between them, add to set
                         if let Some(mv) = self.get_specific_move_between_tableaus(
                             from_tableau_idx,
-                            first_unlocked_idx as usize,
+                            index,
                             to_tableau_idx,
                         ) {
                             set.insert(mv);
                         }
                     }
-                });
-
-            // Check rest of the stack, only if it opens a card that can move to a foundation
-            for index in (first_unlocked_idx as usize + 1)..self.tableaus[from_tableau_idx].0.len()
-            {
-                if self.can_move_card_to_foundation(self.tableaus[from_tableau_idx].0[index - 1]) {
-                    self.tableaus
-                        .iter()
-                        .enumerate()
-                        .for_each(|(to_tableau_idx, _)| {
-                            if from_tableau_idx != to_tableau_idx {
-                                if let Some(mv) = self.get_specific_move_between_tableaus(
-                                    from_tableau_idx,
-                                    index,
-                                    to_tableau_idx,
-                                ) {
-                                    set.insert(mv);
-                                }
-                            }
-                        });
                 }
             }
         } else {
@@ -206,11 +423,8 @@ impl Game {
             // If the tableau is not empty, check if we can move that card to a foundation
             if self.can_move_card_to_foundation(*from_tableau_card) {
                 Some(Move {
-                    from: CardPosition::Tableau((
-                        from_tableau_idx as u8,
-                        (self.tableaus[from_tableau_idx].0.len() - 1) as u8,
-                    )),
-                    to: CardPosition::Foundation(suit_rank(*from_tableau_card)),
+                    from: CardPosition::Tableau((from_tableau_idx as u8, 0)),
+                    to: CardPosition::Foundation(0),
                 })
             } else {
                 None
@@ -220,7 +434,7 @@ impl Game {
         }
     }
 
-    fn get_moves_from_tableau(&self) -> HashSet<Move> {
+    fn get_moves_from_tableau(&self, generation: MoveGeneration) -> HashSet<Move> {
         let mut set = HashSet::new();
         // For every tableau
         for (from_tableau_idx, _) in self.tableaus.iter().enumerate() {
@@ -229,20 +443,134 @@ impl Game {
                 set.insert(mv);
             }
             // Get the moves from this tableau to another tableau
-            set.extend(self.get_tableau_moves_from_tableau(from_tableau_idx));
+            set.extend(self.get_tableau_moves_from_tableau(from_tableau_idx, generation));
         }
         set
     }
 
+    // The moves the solver searches by default, see `MoveGeneration::Pruned`
     pub fn valid_moves(&self) -> HashSet<Move> {
+        self.moves(MoveGeneration::Pruned)
+    }
+
+    pub fn moves(&self, generation: MoveGeneration) -> HashSet<Move> {
         let mut valid_moves = HashSet::new();
         valid_moves.extend(self.get_moves_from_waste());
-        valid_moves.extend(self.get_moves_from_tableau());
+        valid_moves.extend(self.get_moves_from_tableau(generation));
         if let Some(mv) = self.get_move_from_stock() {
             valid_moves.insert(mv);
         }
         valid_moves
     }
+
+    // The legal move that plays like `mv`, which may leave out indices as its short notation
+    // does
+    pub fn resolve_move(&self, mv: &Move) -> Option<Move> {
+        let normalized = mv.normalized();
+        self.moves(MoveGeneration::Complete)
+            .into_iter()
+            .find(|valid| valid.normalized() == normalized)
+    }
+
+    // Plays `mv` after checking that it is legal, unlike `handle_move` which trusts the move
+    // generators. For moves read from outside, like a recorded solution.
+    pub fn play_move(&self, mv: &Move) -> Result<Self, MoveError> {
+        let resolved = self.resolve_move(mv).ok_or(MoveError::IllegalMove(*mv))?;
+        self.handle_move(&resolved)
+    }
 }
 
 // TODO: Moves to two different empty spaces are the same. Reduce that symmetry
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{deal::Deal, rules::Rules};
+
+    // Every legal move met along a fixed walk through a few deals
+    fn moves_along_walks() -> Vec<(Game, Move)> {
+        let mut moves = Vec::new();
+        for seed in 0..8 {
+            let mut game = Game::from_deal(&Deal::from_seed(seed), Rules::default());
+            for step in 0..100 {
+                let mut valid_moves = game
+                    .moves(MoveGeneration::Complete)
+                    .into_iter()
+                    .collect::<Vec<_>>();
+                if valid_moves.is_empty() {
+                    break;
+                }
+                valid_moves.sort_unstable();
+                moves.extend(valid_moves.iter().map(|mv| (game.clone(), *mv)));
+                game = game
+                    .handle_move(&valid_moves[step % valid_moves.len()])
+                    .unwrap();
+            }
+        }
+        moves
+    }
+
+    #[test]
+    fn notation_round_trips() {
+        for (_, mv) in moves_along_walks() {
+            assert_eq!(mv, mv.normalized());
+            assert_eq!(mv.to_string().parse(), Ok(mv));
+            assert_eq!(mv.from.to_string().parse(), Ok(mv.from));
+            assert_eq!(mv.to.to_string().parse(), Ok(mv.to));
+        }
+    }
+
+    #[test]
+    fn moves_are_written_short() {
+        let mv = |from, to| Move { from, to }.to_string();
+        assert_eq!(mv(CardPosition::Stock, CardPosition::Waste), "S");
+        assert_eq!(mv(CardPosition::Waste, CardPosition::Stock), "W-S");
+        assert_eq!(
+            mv(CardPosition::Waste, CardPosition::Tableau((3, 4))),
+            "W-T3"
+        );
+        assert_eq!(mv(CardPosition::Waste, CardPosition::Foundation(2)), "W-F");
+        assert_eq!(
+            mv(CardPosition::Tableau((5, 2)), CardPosition::Foundation(1)),
+            "T5-F"
+        );
+        assert_eq!(
+            mv(CardPosition::Tableau((2, 4)), CardPosition::Tableau((6, 5))),
+            "T2:4-T6"
+        );
+    }
+
+    #[test]
+    fn full_positions_parse_to_the_short_move() {
+        for (full, short) in [
+            ("W-T3:4", "W-T3"),
+            ("W-F2", "W-F"),
+            ("T5:2-F1", "T5-F"),
+            ("T2:4-T6:5", "T2:4-T6"),
+        ] {
+            assert_eq!(full.parse::<Move>(), short.parse::<Move>(), "{}", full);
+        }
+    }
+
+    #[test]
+    fn legal_moves_resolve_to_themselves() {
+        for (game, mv) in moves_along_walks() {
+            assert_eq!(game.resolve_move(&mv), Some(mv), "{}", mv);
+            assert_eq!(
+                game.play_move(&mv).unwrap(),
+                game.handle_move(&mv).unwrap(),
+                "{}",
+                mv
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_notation_is_rejected() {
+        for s in [
+            "", "X", "T2-T6", "T7:0-F", "T0:0-F4", "W", "W-", "T-F", "T0:x-T1",
+        ] {
+            assert!(s.parse::<Move>().is_err(), "{}", s);
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use crossbeam_deque::{Injector, Stealer, Worker};
+use dashmap::DashSet;
+use log::{debug, error};
+use std::{
+    iter,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    deal::Deal,
+    moves::Move,
+    results::{Deduplication, Outcome, SolveResult},
+    rules::Rules,
+    solver::{GameCompact, SearchConfig, SearchStats, Solver},
+    strategy::{path_moves, Path, PathNode, Strategy},
+    Game,
+};
+
+struct Task {
+    depth: u32,
+    game: Game,
+    path: Path,
+}
+
+// What the workers share. `pending` counts the tasks pushed but not yet expanded, so the
+// search space is exhausted once it drops to zero.
+struct Shared {
+    visited: DashSet<GameCompact>,
+    injector: Injector<Task>,
+    stealers: Vec<Stealer<Task>>,
+    pending: AtomicUsize,
+    expanded_nodes: AtomicU64,
+    stop: AtomicBool,
+    // What the first worker to stop the search found
+    decided: Mutex<Option<Decision>>,
+}
+
+enum Decision {
+    Solved(Vec<Move>),
+    // Out of budget or cancelled
+    Stopped(Outcome),
+    Failed(String),
+}
+
+impl Shared {
+    fn decide(&self, decision: Decision) {
+        let mut decided = self.decided.lock().unwrap();
+        if decided.is_none() {
+            *decided = Some(decision);
+        }
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    // The next task of a worker: its own newest one, else a batch from the injector, else
+    // one stolen from another worker
+    fn find_task(&self, local: &Worker<Task>) -> Option<Task> {
+        local.pop().or_else(|| {
+            iter::repeat_with(|| {
+                self.injector
+                    .steal_batch_and_pop(local)
+                    .or_else(|| self.stealers.iter().map(Stealer::steal).collect())
+            })
+            .find(|steal| !steal.is_retry())
+            .and_then(|steal| steal.success())
+        })
+    }
+}
+
+// Searches a single deal on several threads. Workers run depth-first on their own deque and
+// steal from each other when they run dry, sharing one transposition table, so the visited
+// states are expanded once across all of them. The solution is replayed before it is
+// returned, and the search order, unlike `Solver`'s, changes from run to run.
+pub struct ParallelSolver {
+    config: SearchConfig,
+    threads: usize,
+}
+
+impl ParallelSolver {
+    pub fn new(config: SearchConfig) -> Self {
+        Self {
+            config,
+            threads: thread::available_parallelism().map_or(1, usize::from),
+        }
+    }
+
+    // A search of `deal` with the default settings
+    pub fn from_deal(deal: Deal, rules: Rules) -> Self {
+        Self::new(SearchConfig::from_deal(deal, rules))
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn solve(self) -> SolveResult {
+        let timer = Instant::now();
+        debug!(
+            "Solving seed {} on {} threads:\n{}",
+            self.config.deal.seed, self.threads, self.config.original_game
+        );
+        let workers = (0..self.threads)
+            .map(|_| Worker::new_lifo())
+            .collect::<Vec<_>>();
+        let shared = Shared {
+            visited: DashSet::new(),
+            injector: Injector::new(),
+            stealers: workers.iter().map(Worker::stealer).collect(),
+            pending: AtomicUsize::new(1),
+            expanded_nodes: AtomicU64::new(0),
+            stop: AtomicBool::new(false),
+            decided: Mutex::new(None),
+        };
+        shared.injector.push(Task {
+            depth: 0,
+            game: self.config.original_game.clone(),
+            path: None,
+        });
+
+        let worker_stats = thread::scope(|scope| {
+            let handles = workers
+                .into_iter()
+                .map(|local| scope.spawn(|| self.work(&shared, local, timer)))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("search worker panicked"))
+                .collect::<Vec<_>>()
+        });
+
+        let mut stats = SearchStats::default();
+        for worker in worker_stats {
+            stats.expanded_nodes += worker.expanded_nodes;
+            stats.generated_nodes += worker.generated_nodes;
+            stats.duplicates += worker.duplicates;
+            stats.dead_ends += worker.dead_ends;
+            stats.deadlocks += worker.deadlocks;
+            stats.max_depth = stats.max_depth.max(worker.max_depth);
+            stats.peak_frontier_size = stats.peak_frontier_size.max(worker.peak_frontier_size);
+        }
+        stats.peak_visited_size = shared.visited.len();
+        let decision = shared
+            .decided
+            .into_inner()
+            .unwrap()
+            .unwrap_or(Decision::Stopped(Outcome::Unsolvable));
+        self.result(decision, stats, timer.elapsed())
+    }
+
+    // Runs one worker until the deal is decided or the search space is exhausted, returning
+    // the stats of the states it expanded. `peak_frontier_size` counts the pending tasks of
+    // every worker.
+    fn work(&self, shared: &Shared, local: Worker<Task>, timer: Instant) -> SearchStats {
+        let mut stats = SearchStats::default();
+        while !shared.stop.load(Ordering::Relaxed) {
+            let Some(Task { depth, game, path }) = shared.find_task(&local) else {
+                if shared.pending.load(Ordering::Acquire) == 0 {
+                    break;
+                }
+                thread::yield_now();
+                continue;
+            };
+            if game.is_game_won() {
+                shared.decide(Decision::Solved(path_moves(&path)));
+                break;
+            }
+            // Another worker may have expanded an equivalent state since this one was pushed
+            if !shared.visited.insert(game.compact_state()) {
+                stats.duplicates += 1;
+                shared.pending.fetch_sub(1, Ordering::Release);
+                continue;
+            }
+            stats.expanded_nodes += 1;
+            stats.max_depth = stats.max_depth.max(depth);
+            let expanded_nodes = shared.expanded_nodes.fetch_add(1, Ordering::Relaxed) + 1;
+            let valid_moves = game.moves(self.config.move_generation);
+            if Solver::is_game_lost(&valid_moves) {
+                stats.dead_ends += 1;
+            } else if self.config.prune_deadlocks && game.is_deadlocked() {
+                stats.deadlocks += 1;
+            } else {
+                for mv in valid_moves {
+                    let next_game = match game.handle_move(&mv) {
+                        Ok(next_game) => next_game,
+                        Err(err) => {
+                            error!(
+                                "Seed {}: {} failed: {}\n{}",
+                                self.config.deal.seed, mv, err, game
+                            );
+                            shared.decide(Decision::Failed(format!("{} failed: {}", mv, err)));
+                            return stats;
+                        }
+                    };
+                    stats.generated_nodes += 1;
+                    if shared.visited.contains(&next_game.compact_state()) {
+                        stats.duplicates += 1;
+                        continue;
+                    }
+                    shared.pending.fetch_add(1, Ordering::Relaxed);
+                    local.push(Task {
+                        depth: depth + 1,
+                        game: next_game,
+                        path: Some(Arc::new(PathNode {
+                            mv,
+                            parent: path.clone(),
+                        })),
+                    });
+                }
+            }
+            let pending = shared.pending.fetch_sub(1, Ordering::Release) - 1;
+            stats.peak_frontier_size = stats.peak_frontier_size.max(pending);
+
+            if self
+                .config
+                .is_out_of_budget(expanded_nodes, timer.elapsed())
+            {
+                shared.decide(Decision::Stopped(Outcome::Timeout));
+            } else if self.config.is_cancelled() {
+                shared.decide(Decision::Stopped(Outcome::Cancelled));
+            }
+        }
+        stats
+    }
+
+    fn result(self, decision: Decision, stats: SearchStats, elapsed: Duration) -> SolveResult {
+        let (outcome, solution, error) = match decision {
+            Decision::Solved(solution) => {
+                // Paths are put together across workers, so make sure this one really wins
+                let replayed = solution
+                    .iter()
+                    .try_fold(self.config.original_game.clone(), |game, mv| {
+                        game.handle_move(mv)
+                    });
+                if replayed.is_ok_and(|game| game.is_game_won()) {
+                    (Outcome::Solved, solution, None)
+                } else {
+                    let error = "the solution found doesn't replay".to_string();
+                    error!("Seed {}: {}", self.config.deal.seed, error);
+                    (Outcome::Error, Vec::new(), Some(error))
+                }
+            }
+            Decision::Stopped(outcome) => (outcome, Vec::new(), None),
+            Decision::Failed(error) => (Outcome::Error, Vec::new(), Some(error)),
+        };
+        SolveResult {
+            error,
+            ..self.config.result(
+                outcome,
+                solution,
+                stats,
+                elapsed,
+                Deduplication::Exact,
+                Strategy::Dfs,
+            )
+        }
+    }
+}
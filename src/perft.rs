@@ -0,0 +1,65 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    moves::{MoveError, MoveGeneration},
+    solver::GameCompact,
+    Game,
+};
+
+// What `perft` finds at one depth of the move tree
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PerftCounts {
+    pub depth: u32,
+    // Move sequences of exactly this length, so transpositions are counted once per path
+    pub paths: u64,
+    // Distinct positions those sequences end in, as told apart by `Game::compact_state`
+    pub positions: usize,
+}
+
+impl fmt::Display for PerftCounts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "depth {}: {} paths, {} positions",
+            self.depth, self.paths, self.positions
+        )
+    }
+}
+
+// Counts the move sequences of every length up to `depth` from `game`, and the positions
+// they reach. Any change to move generation, canonicalization or the rules shows up as a
+// change in these counts.
+//
+// Positions are expanded once per depth, with the number of paths reaching them, so the
+// cost grows with the number of positions rather than the number of paths.
+pub fn perft(
+    game: &Game,
+    depth: u32,
+    generation: MoveGeneration,
+) -> Result<Vec<PerftCounts>, MoveError> {
+    let mut level = HashMap::from([(game.compact_state(), (game.clone(), 1))]);
+    let mut counts = vec![PerftCounts {
+        depth: 0,
+        paths: 1,
+        positions: 1,
+    }];
+    for depth in 1..=depth {
+        let mut next_level: HashMap<GameCompact, (Game, u64)> = HashMap::new();
+        for (game, paths) in level.values() {
+            for mv in game.moves(generation) {
+                let next_game = game.handle_move(&mv)?;
+                next_level
+                    .entry(next_game.compact_state())
+                    .or_insert((next_game, 0))
+                    .1 += paths;
+            }
+        }
+        counts.push(PerftCounts {
+            depth,
+            paths: next_level.values().map(|(_, paths)| paths).sum(),
+            positions: next_level.len(),
+        });
+        level = next_level;
+    }
+    Ok(counts)
+}
@@ -0,0 +1,289 @@
+use std::{collections::HashMap, fmt};
+
+use crate::results::{Outcome, SolveResult};
+
+// Wilson score interval of a proportion of `successes` out of `trials`, for the normal
+// quantile `z` (1.96 for 95% confidence). Unlike the normal approximation it stays within
+// [0, 1] and behaves well for rates close to 0 or 1 and for small samples.
+pub fn wilson_interval(successes: usize, trials: usize, z: f64) -> (f64, f64) {
+    if trials == 0 {
+        return (0.0, 1.0);
+    }
+    let n = trials as f64;
+    let p = successes as f64 / n;
+    let z2 = z * z;
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let half_width = z / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+    (
+        (center - half_width).max(0.0),
+        (center + half_width).min(1.0),
+    )
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OutcomeCounts {
+    pub solved: usize,
+    pub unsolvable: usize,
+    // Timed out, cancelled or failed
+    pub unknown: usize,
+}
+
+impl OutcomeCounts {
+    pub fn add(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Solved => self.solved += 1,
+            Outcome::Unsolvable => self.unsolvable += 1,
+            Outcome::Timeout | Outcome::Cancelled | Outcome::Error => self.unknown += 1,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.solved + self.unsolvable + self.unknown
+    }
+
+    // Width of the interval on the solvable rate, used to stop a batch early
+    pub fn solvable_interval_width(&self, z: f64) -> f64 {
+        let (low, high) = wilson_interval(self.solved, self.total(), z);
+        high - low
+    }
+}
+
+// Count of values per bucket, printed as a bar chart
+pub struct Histogram {
+    buckets: Vec<(String, usize)>,
+}
+
+impl Histogram {
+    // Buckets of `width` consecutive values, from the smallest to the largest value
+    pub fn linear(values: &[u64], width: u64) -> Self {
+        let min = values.iter().copied().min().unwrap_or(0);
+        let max = values.iter().copied().max().unwrap_or(0);
+        let buckets = (min / width..=max / width)
+            .map(|bucket| {
+                let start = bucket * width;
+                let count = values.iter().filter(|v| **v / width == bucket).count();
+                (format!("{}-{}", start, start + width - 1), count)
+            })
+            .collect();
+        Self { buckets }
+    }
+
+    // Buckets of powers of 10, for values spanning several orders of magnitude
+    pub fn log10(values: &[u64]) -> Self {
+        let magnitude = |value: u64| value.max(1).ilog10();
+        let min = values.iter().map(|v| magnitude(*v)).min().unwrap_or(0);
+        let max = values.iter().map(|v| magnitude(*v)).max().unwrap_or(0);
+        let buckets = (min..=max)
+            .map(|power| {
+                let count = values.iter().filter(|v| magnitude(**v) == power).count();
+                (format!("1e{}-1e{}", power, power + 1), count)
+            })
+            .collect();
+        Self { buckets }
+    }
+}
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const BAR_WIDTH: usize = 50;
+        let max_count = self
+            .buckets
+            .iter()
+            .map(|(_, count)| *count)
+            .max()
+            .unwrap_or(0);
+        let label_width = self
+            .buckets
+            .iter()
+            .map(|(label, _)| label.len())
+            .max()
+            .unwrap_or(0);
+        self.buckets.iter().try_for_each(|(label, count)| {
+            let bar = if max_count > 0 {
+                (count * BAR_WIDTH).div_ceil(max_count)
+            } else {
+                0
+            };
+            writeln!(f, "{:>label_width$} | {} {}", label, "#".repeat(bar), count)
+        })
+    }
+}
+
+// Nearest-rank percentile of already sorted values
+fn percentile(sorted: &[u64], percent: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = ((percent / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+pub struct Report {
+    z: f64,
+    counts: OutcomeCounts,
+    // Unsolvable deals whose search could not have skipped a state, see `SolveResult::is_proof`
+    proven_unsolvable: usize,
+    // Of which proven by a search under relaxed rules, see `Solver::with_relaxed_check`
+    proven_by_relaxation: usize,
+    solution_lengths: Vec<u64>,
+    expanded_nodes: Vec<u64>,
+    elapsed_ms: Vec<u64>,
+}
+
+impl Report {
+    // Results for the same seed replace each other, so that the result of a retry
+    // supersedes the timeout it was retrying when both files are passed in order
+    pub fn new<'a>(results: impl IntoIterator<Item = &'a SolveResult>, z: f64) -> Self {
+        let latest = results
+            .into_iter()
+            .map(|result| (result.deal.seed, result))
+            .collect::<HashMap<_, _>>();
+        let mut counts = OutcomeCounts::default();
+        let mut proven_unsolvable = 0;
+        let mut proven_by_relaxation = 0;
+        let mut solution_lengths = Vec::new();
+        let mut expanded_nodes = Vec::new();
+        let mut elapsed_ms = Vec::new();
+        for result in latest.values() {
+            counts.add(result.outcome);
+            if result.outcome == Outcome::Unsolvable && result.is_proof() {
+                proven_unsolvable += 1;
+                if result.relaxation.is_some() {
+                    proven_by_relaxation += 1;
+                }
+            }
+            if result.outcome == Outcome::Solved {
+                solution_lengths.push(result.solution.len() as u64);
+            }
+            expanded_nodes.push(result.stats.expanded_nodes);
+            elapsed_ms.push(result.stats.elapsed_ms);
+        }
+        elapsed_ms.sort_unstable();
+        Self {
+            z,
+            counts,
+            proven_unsolvable,
+            proven_by_relaxation,
+            solution_lengths,
+            expanded_nodes,
+            elapsed_ms,
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = self.counts.total();
+        writeln!(f, "Deals: {}", total)?;
+        for (name, count) in [
+            ("Solvable", self.counts.solved),
+            ("Unsolvable", self.counts.unsolvable),
+            ("Unknown", self.counts.unknown),
+        ] {
+            let (low, high) = wilson_interval(count, total, self.z);
+            writeln!(
+                f,
+                "{:<10} {:>8} {:>7.3}%  [{:.3}%, {:.3}%]",
+                name,
+                count,
+                100.0 * count as f64 / total.max(1) as f64,
+                100.0 * low,
+                100.0 * high
+            )?;
+        }
+        writeln!(
+            f,
+            "{} of the unsolvable deals are proven so, {} of them under relaxed rules. The \
+             others were searched with pruned moves, an approximate visited set or a beam",
+            self.proven_unsolvable, self.proven_by_relaxation
+        )?;
+        writeln!(f, "\nSolution lengths of solved deals:")?;
+        write!(f, "{}", Histogram::linear(&self.solution_lengths, 20))?;
+        writeln!(f, "\nExpanded states:")?;
+        write!(f, "{}", Histogram::log10(&self.expanded_nodes))?;
+        writeln!(f, "\nTime per deal (ms):")?;
+        writeln!(
+            f,
+            "p50: {}, p90: {}, p99: {}, max: {}",
+            percentile(&self.elapsed_ms, 50.0),
+            percentile(&self.elapsed_ms, 90.0),
+            percentile(&self.elapsed_ms, 99.0),
+            self.elapsed_ms.last().copied().unwrap_or(0)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: (f64, f64), expected: (f64, f64)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-4 && (actual.1 - expected.1).abs() < 1e-4,
+            "{:?} instead of {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn wilson_interval_of_all_or_nothing() {
+        // z² / (n + z²) for 10 trials at 95%
+        let width = 1.96 * 1.96 / (10.0 + 1.96 * 1.96);
+        assert_close(wilson_interval(0, 10, 1.96), (0.0, width));
+        assert_close(wilson_interval(10, 10, 1.96), (1.0 - width, 1.0));
+        assert_eq!(wilson_interval(0, 0, 1.96), (0.0, 1.0));
+    }
+
+    #[test]
+    fn wilson_interval_of_half() {
+        assert_close(wilson_interval(50, 100, 1.96), (0.4038, 0.5962));
+    }
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let values = [1, 2, 3, 4];
+        assert_eq!(percentile(&values, 0.0), 1);
+        assert_eq!(percentile(&values, 25.0), 1);
+        assert_eq!(percentile(&values, 26.0), 2);
+        assert_eq!(percentile(&values, 50.0), 2);
+        assert_eq!(percentile(&values, 100.0), 4);
+        assert_eq!(percentile(&[7], 90.0), 7);
+        assert_eq!(percentile(&[], 50.0), 0);
+    }
+
+    #[test]
+    fn linear_histogram_bin_edges() {
+        assert_eq!(
+            Histogram::linear(&[0, 9, 10, 25], 10).buckets,
+            [
+                ("0-9".to_string(), 2),
+                ("10-19".to_string(), 1),
+                ("20-29".to_string(), 1)
+            ]
+        );
+        // Starts at the bucket of the smallest value, and keeps empty buckets in between
+        assert_eq!(
+            Histogram::linear(&[15, 34], 10).buckets,
+            [
+                ("10-19".to_string(), 1),
+                ("20-29".to_string(), 0),
+                ("30-39".to_string(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn log10_histogram_bin_edges() {
+        // 0 counts with 1, in the first bucket
+        assert_eq!(
+            Histogram::log10(&[0, 1, 9, 10, 999, 1000]).buckets,
+            [
+                ("1e0-1e1".to_string(), 3),
+                ("1e1-1e2".to_string(), 1),
+                ("1e2-1e3".to_string(), 1),
+                ("1e3-1e4".to_string(), 1)
+            ]
+        );
+    }
+}
@@ -0,0 +1,347 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use crate::{
+    deal::Deal,
+    moves::{Move, MoveError, MoveGeneration},
+    rules::{Relaxation, Rules},
+    solver::{Budget, SearchStats},
+    strategy::Strategy,
+    Game,
+};
+
+// Bump whenever the serialized layout of the types below changes
+pub const RESULT_SCHEMA_VERSION: u32 = 14;
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Solved,
+    // The search space was exhausted without reaching a won state
+    Unsolvable,
+    // The search ran out of budget before it could decide either way
+    Timeout,
+    // The search was stopped from outside before it could decide either way
+    Cancelled,
+    // A move failed and left the search unable to decide either way, see `SolveResult::error`
+    Error,
+}
+
+// How a search told visited states apart
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Deduplication {
+    #[default]
+    Exact,
+    // In a Bloom filter of this size, which can prune states never visited
+    Bloom {
+        memory_bytes: usize,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SolveResult {
+    pub version: u32,
+    pub deal: Deal,
+    pub rules: Rules,
+    // The budget of the search that produced this result
+    pub budget: Budget,
+    // The moves the search considered. `Unsolvable` is only a proof with `Complete`.
+    pub move_generation: MoveGeneration,
+    pub deduplication: Deduplication,
+    // The order states were expanded in. `Unsolvable` is no proof after a beam search.
+    pub strategy: Strategy,
+    // Set when the deal was proven unsolvable by exhausting a search under these relaxed
+    // rules, see `Solver::with_relaxed_check`. The stats are those of that search.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relaxation: Option<Relaxation>,
+    pub outcome: Outcome,
+    pub stats: SearchStats,
+    // Empty unless the outcome is `Solved`
+    pub solution: Vec<Move>,
+    // Why the search failed, if the outcome is `Error`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl SolveResult {
+    // Rebuilds the game as it was dealt, before any move of the solution was made
+    pub fn game(&self) -> Game {
+        Game::from_deal(&self.deal, self.rules)
+    }
+
+    // Whether the outcome settles the deal. A solution always does, being replayable, but
+    // an exhausted search only does if it could not have skipped any state. The relaxed
+    // check never skips one.
+    pub fn is_proof(&self) -> bool {
+        match self.outcome {
+            Outcome::Solved => true,
+            Outcome::Unsolvable => {
+                self.relaxation.is_some()
+                    || self.move_generation == MoveGeneration::Complete
+                        && self.deduplication == Deduplication::Exact
+                        && self.strategy.is_exhaustive()
+            }
+            Outcome::Timeout | Outcome::Cancelled | Outcome::Error => false,
+        }
+    }
+
+    // Plays the solution from the start of the deal, returning the final state
+    pub fn replay(&self) -> Result<Game, MoveError> {
+        self.solution
+            .iter()
+            .try_fold(self.game(), |game, mv| game.play_move(mv))
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Json { line: usize, err: serde_json::Error },
+    UnsupportedVersion { line: usize, version: u32 },
+    InvalidDeal { line: usize, seed: u32 },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "could not read results: {}", err),
+            LoadError::Json { line, err } => {
+                write!(f, "line {}: could not parse result: {}", line, err)
+            }
+            LoadError::UnsupportedVersion { line, version } => write!(
+                f,
+                "line {}: unsupported result schema version {}, expected {}",
+                line, version, RESULT_SCHEMA_VERSION
+            ),
+            LoadError::InvalidDeal { line, seed } => {
+                write!(
+                    f,
+                    "line {}: deal with seed {} is not a full deck",
+                    line, seed
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> Self {
+        LoadError::Io(err)
+    }
+}
+
+// Reads a JSON Lines file of results, one `SolveResult` per line. A last line that doesn't
+// parse was cut short by a crash while it was written, and is skipped with a warning like
+// `completed_deals` skips it. Anywhere else it is an error.
+pub fn read_results(path: impl AsRef<Path>) -> Result<Vec<SolveResult>, LoadError> {
+    let mut results = Vec::new();
+    let mut unparsed = None;
+    // Read a line at a time, as the results of a large batch run to gigabytes
+    for (idx, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(err) = unparsed.take() {
+            return Err(err);
+        }
+        let line_number = idx + 1;
+        let result: SolveResult = match serde_json::from_str(&line) {
+            Ok(result) => result,
+            Err(err) => {
+                unparsed = Some(LoadError::Json {
+                    line: line_number,
+                    err,
+                });
+                continue;
+            }
+        };
+        if result.version != RESULT_SCHEMA_VERSION {
+            return Err(LoadError::UnsupportedVersion {
+                line: line_number,
+                version: result.version,
+            });
+        }
+        if !result.deal.is_full_deck() {
+            return Err(LoadError::InvalidDeal {
+                line: line_number,
+                seed: result.deal.seed,
+            });
+        }
+        results.push(result);
+    }
+    if let Some(err) = unparsed {
+        warn!("Skipping the last result, which was cut short: {}", err);
+    }
+    Ok(results)
+}
+
+// Appends records to a JSON Lines file as they come in, so an interrupted batch keeps
+// every deal that finished. Can be shared between rayon workers.
+pub struct JsonLinesWriter {
+    file: Mutex<File>,
+}
+
+impl JsonLinesWriter {
+    pub fn append(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        // Terminate a line left unfinished by an interrupted run, so the next record starts clean
+        if file.seek(SeekFrom::End(0))? > 0 {
+            let mut last_byte = [0; 1];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last_byte)?;
+            if last_byte[0] != b'\n' {
+                file.write_all(b"\n")?;
+            }
+        }
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn write(&self, record: &impl Serialize) -> io::Result<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        // A single write per record keeps lines whole even if several workers finish together
+        let mut file = self.file.lock().unwrap();
+        file.write_all(line.as_bytes())?;
+        file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::Solver;
+    use std::fs;
+
+    fn solved_result() -> SolveResult {
+        let result = Solver::from_deal(Deal::from_seed(1), Rules::default()).solve();
+        assert_eq!(result.outcome, Outcome::Solved);
+        result
+    }
+
+    // Writes `results` to a file of their own and reads them back
+    fn write_and_read(name: &str, results: &[SolveResult]) -> Result<Vec<SolveResult>, LoadError> {
+        let path = std::env::temp_dir().join(format!("{}-{}.jsonl", name, std::process::id()));
+        let writer = JsonLinesWriter::append(&path).unwrap();
+        for result in results {
+            writer.write(result).unwrap();
+        }
+        drop(writer);
+        let read = read_results(&path);
+        fs::remove_file(&path).unwrap();
+        read
+    }
+
+    #[test]
+    fn results_round_trip_through_json() {
+        let result = solved_result();
+        let json = serde_json::to_string(&result).unwrap();
+        assert_eq!(serde_json::from_str::<SolveResult>(&json).unwrap(), result);
+    }
+
+    #[test]
+    fn results_of_another_version_are_rejected() {
+        let old = SolveResult {
+            version: RESULT_SCHEMA_VERSION - 1,
+            ..solved_result()
+        };
+        let read = write_and_read("old-version", &[solved_result(), old]);
+        assert!(matches!(
+            read,
+            Err(LoadError::UnsupportedVersion { line: 2, version }) if version == RESULT_SCHEMA_VERSION - 1
+        ));
+    }
+
+    #[test]
+    fn results_of_broken_deals_are_rejected() {
+        let mut broken = solved_result();
+        broken.deal.cards[0] = broken.deal.cards[1];
+        let read = write_and_read("broken-deal", &[broken]);
+        assert!(matches!(
+            read,
+            Err(LoadError::InvalidDeal { line: 1, seed: 1 })
+        ));
+    }
+
+    #[test]
+    fn a_truncated_last_result_is_skipped() {
+        let path = std::env::temp_dir().join(format!("truncated-{}.jsonl", std::process::id()));
+        let line = serde_json::to_string(&solved_result()).unwrap();
+        let cut = &line[..line.len() / 2];
+        fs::write(&path, format!("{}\n{}\n{}", line, line, cut)).unwrap();
+        let last_skipped = read_results(&path).map(|results| results.len());
+        fs::write(&path, format!("{}\n{}\n{}\n", line, cut, line)).unwrap();
+        let middle_rejected = read_results(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(last_skipped.unwrap(), 2);
+        assert!(matches!(
+            middle_rejected,
+            Err(LoadError::Json { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn appending_keeps_earlier_records_whole() {
+        let path = std::env::temp_dir().join(format!("appended-{}.jsonl", std::process::id()));
+        let result = solved_result();
+        let writer = JsonLinesWriter::append(&path).unwrap();
+        writer.write(&result).unwrap();
+        writer.write(&result).unwrap();
+        drop(writer);
+        let writer = JsonLinesWriter::append(&path).unwrap();
+        writer.write(&result).unwrap();
+        drop(writer);
+        let text = fs::read_to_string(&path).unwrap();
+        let read = read_results(&path);
+
+        // A record written after an interrupted one starts on a line of its own
+        let line = serde_json::to_string(&result).unwrap();
+        fs::write(&path, &line[..line.len() / 2]).unwrap();
+        let writer = JsonLinesWriter::append(&path).unwrap();
+        writer.write(&result).unwrap();
+        drop(writer);
+        let after_cut = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(text.ends_with('\n'));
+        assert_eq!(text.lines().count(), 3);
+        for line in text.lines() {
+            assert_eq!(serde_json::from_str::<SolveResult>(line).unwrap(), result);
+        }
+        assert_eq!(read.unwrap(), vec![result.clone(); 3]);
+        assert!(after_cut.ends_with('\n'));
+        assert_eq!(after_cut.lines().nth(1), Some(line.as_str()));
+    }
+
+    #[test]
+    fn illegal_moves_fail_to_replay() {
+        for notation in ["W-T0:0", "F0-T1:0", "T0:0-T0:1"] {
+            let mv = notation.parse::<Move>().unwrap();
+            let result = SolveResult {
+                solution: vec![mv],
+                ..solved_result()
+            };
+            assert!(
+                matches!(result.replay(), Err(MoveError::IllegalMove(illegal)) if illegal == mv),
+                "{}",
+                notation
+            );
+        }
+    }
+}
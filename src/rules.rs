@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+use crate::card::NUM_CARDS_SUIT;
+
+pub const NUM_TABLEAUS: usize = 7;
+// The last tableau is dealt with a face-down card under each of the others
+pub const MAX_FACE_DOWN: usize = NUM_TABLEAUS - 1;
+// Room for the tallest tableau the rules can build: every face-down card topped by a full run
+// from king to ace, under any relaxation too. Tableaus are stored inline with this capacity,
+// and moves that would grow a tableau past it fail with `MoveError::TableauOverflow`.
+pub const MAX_TABLEAU_LEN: usize = MAX_FACE_DOWN + NUM_CARDS_SUIT as usize;
+
+// Rule changes that make a deal easier to win, every move of the normal rules staying legal.
+// A deal that can't be won even so can't be won at all.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Relaxation {
+    // A card can go on a tableau card of the next rank whatever their colours
+    pub ignore_colors: bool,
+    // Any card can go on an empty tableau, not only a king
+    pub any_card_on_empty_tableau: bool,
+}
+
+impl FromStr for Relaxation {
+    type Err = String;
+
+    // A comma separated list of `colors` and `empty-tableau`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut relaxation = Relaxation::default();
+        for name in s.split(',') {
+            match name.trim() {
+                "colors" => relaxation.ignore_colors = true,
+                "empty-tableau" => relaxation.any_card_on_empty_tableau = true,
+                name => {
+                    return Err(format!(
+                        "`{}` is not a relaxation, expected `colors` or `empty-tableau`",
+                        name
+                    ))
+                }
+            }
+        }
+        Ok(relaxation)
+    }
+}
+
+impl fmt::Display for Relaxation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = [
+            (self.ignore_colors, "colors"),
+            (self.any_card_on_empty_tableau, "empty-tableau"),
+        ];
+        let names = names.iter().filter(|(on, _)| *on).map(|(_, name)| *name);
+        write!(f, "{}", names.collect::<Vec<_>>().join(","))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rules {
+    // Number of cards moved from the stock to the waste in a single draw
+    pub draw_count: u8,
+    pub relaxation: Relaxation,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            draw_count: 1,
+            relaxation: Relaxation::default(),
+        }
+    }
+}
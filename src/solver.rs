@@ -1,29 +1,471 @@
-use serde::Serialize;
+use log::{debug, error, info, log_enabled, trace, Level};
+use serde::{Deserialize, Serialize};
 
-use crate::{moves::CardPosition, VERBOSE_PRINT};
+use crate::{
+    bloom::BloomFilter,
+    deal::Deal,
+    moves::CardPosition,
+    results::{Deduplication, Outcome, SolveResult, RESULT_SCHEMA_VERSION},
+    rules::{Relaxation, Rules},
+    strategy::{self, PathNode, SearchNode, SearchStrategy, Strategy},
+    validate::InvalidState,
+};
 
 use super::{card::*, moves::*, Game};
-use std::{collections::HashSet, time::Instant};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct GameCompact {
     data: [Card; 52 + 4],
 }
 
-#[derive(Serialize)]
+// Serde only handles arrays of up to 32 elements, so the data goes through a slice
+impl Serialize for GameCompact {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.data.as_slice().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for GameCompact {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = Vec::<Card>::deserialize(deserializer)?;
+        let len = data.len();
+        let data = data
+            .try_into()
+            .map_err(|_| serde::de::Error::invalid_length(len, &"56 bytes"))?;
+        Ok(GameCompact { data })
+    }
+}
+
+pub const DEFAULT_TIME_LIMIT_MS: u64 = 5000;
+
+// States the relaxed check of `with_relaxed_check` expands by default. A relaxed deal that
+// can't be won is usually hopeless from the start, while the others take far more.
+pub const DEFAULT_RELAXED_CHECK_NODES: u64 = 1000;
+
+// How much search `solve` may spend on a deal before giving up with a `Timeout`
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Budget {
+    pub time_limit_ms: u64,
+    // Maximum number of states expanded, unlimited if `None`
+    pub max_nodes: Option<u64>,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self {
+            time_limit_ms: DEFAULT_TIME_LIMIT_MS,
+            max_nodes: None,
+        }
+    }
+}
+
+impl Budget {
+    // A budget of `max_nodes` expanded states, with no time limit
+    pub fn nodes(max_nodes: u64) -> Budget {
+        Budget {
+            time_limit_ms: u64::MAX,
+            max_nodes: Some(max_nodes),
+        }
+    }
+
+    // A budget that never runs out
+    pub fn unlimited() -> Budget {
+        Budget {
+            time_limit_ms: u64::MAX,
+            max_nodes: None,
+        }
+    }
+
+    pub fn time_limit(&self) -> Duration {
+        Duration::from_millis(self.time_limit_ms)
+    }
+
+    // This budget grown by `factor`, without exceeding `cap`. A limit already past `cap` stays
+    // as it is rather than shrinking to it.
+    pub fn scaled(&self, factor: f64, cap: &Budget) -> Budget {
+        let scale = |value: u64, cap: u64| ((value as f64 * factor) as u64).min(cap).max(value);
+        Budget {
+            time_limit_ms: scale(self.time_limit_ms, cap.time_limit_ms),
+            max_nodes: self
+                .max_nodes
+                .map(|max_nodes| scale(max_nodes, cap.max_nodes.unwrap_or(u64::MAX))),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SearchStats {
+    // States taken off the frontier and expanded into their successors
+    pub expanded_nodes: u64,
+    // Successors produced by expanding states, duplicates included
+    pub generated_nodes: u64,
+    // Successors dropped because an equivalent state had already been visited
+    pub duplicates: u64,
+    // Expanded states with no move left but restocking
+    pub dead_ends: u64,
+    // Expanded states left without successors because some cards can never leave their
+    // tableau, see `Game::is_deadlocked`
+    pub deadlocks: u64,
+    pub max_depth: u32,
+    pub peak_frontier_size: usize,
+    pub peak_visited_size: usize,
+    // Average number of successors per expanded state that had not been visited before
+    pub mean_new_successors: f64,
+    // The branching factor b* a uniform tree as deep as the solution, or as the search went
+    // if there is none, would need to hold the expanded states, see
+    // `effective_branching_factor`
+    pub effective_branching_factor: f64,
+    pub elapsed_ms: u64,
+}
+
+impl SearchStats {
+    // Updates the fields derived from the counters, before the stats are handed out. `depth`
+    // is that of the solution, or the deepest state searched while there is none.
+    pub(crate) fn refresh(&mut self, elapsed: Duration, depth: u32) {
+        self.mean_new_successors = if self.expanded_nodes > 0 {
+            (self.generated_nodes - self.duplicates) as f64 / self.expanded_nodes as f64
+        } else {
+            0.0
+        };
+        self.effective_branching_factor = effective_branching_factor(self.expanded_nodes, depth);
+        self.elapsed_ms = elapsed.as_millis() as u64;
+    }
+}
+
+// Solves N + 1 = 1 + b* + b*^2 + ... + b*^d for b*, with N the `nodes` expanded and d the
+// `depth`, by bisection. The sum grows with b*, and b* = N already makes it reach N + 1.
+fn effective_branching_factor(nodes: u64, depth: u32) -> f64 {
+    if nodes == 0 || depth == 0 {
+        return 0.0;
+    }
+    let nodes = nodes as f64;
+    // b* + ... + b*^d, stopping early once it passes `nodes`
+    let tree_size = |branching: f64| {
+        let (mut size, mut level) = (0.0, 1.0);
+        for _ in 0..depth {
+            level *= branching;
+            size += level;
+            if size > nodes {
+                break;
+            }
+        }
+        size
+    };
+    let (mut low, mut high) = (0.0, nodes);
+    for _ in 0..100 {
+        let mid = (low + high) / 2.0;
+        if tree_size(mid) < nodes {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.0
+}
+
+// Lets another thread stop a running `solve`, which then returns a `Cancelled` result
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// What every solver of a single deal is set up with, whatever order it searches in
+pub struct SearchConfig {
+    pub(crate) deal: Deal,
+    pub(crate) original_game: Game,
+    pub(crate) budget: Budget,
+    pub(crate) move_generation: MoveGeneration,
+    pub(crate) prune_deadlocks: bool,
+    pub(crate) cancellation: Option<CancellationToken>,
+}
+
+impl SearchConfig {
+    pub fn from_deal(deal: Deal, rules: Rules) -> Self {
+        Self {
+            original_game: Game::from_deal(&deal, rules),
+            deal,
+            budget: Budget::default(),
+            move_generation: MoveGeneration::default(),
+            prune_deadlocks: true,
+            cancellation: None,
+        }
+    }
+
+    pub fn deal(&self) -> &Deal {
+        &self.deal
+    }
+
+    pub fn with_budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    pub fn with_move_generation(mut self, generation: MoveGeneration) -> Self {
+        self.move_generation = generation;
+        self
+    }
+
+    // Stops at states where some cards can never leave their tableau, see
+    // `Game::is_deadlocked`. On by default, turning it off shows what it saves.
+    pub fn with_deadlock_pruning(mut self, enabled: bool) -> Self {
+        self.prune_deadlocks = enabled;
+        self
+    }
+
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    pub(crate) fn is_out_of_budget(&self, expanded_nodes: u64, elapsed: Duration) -> bool {
+        self.budget
+            .max_nodes
+            .is_some_and(|max_nodes| expanded_nodes >= max_nodes)
+            || elapsed >= self.budget.time_limit()
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    // Logs how the search ended and puts its result together, without an error or a
+    // relaxation, which the caller fills in
+    pub(crate) fn result(
+        self,
+        outcome: Outcome,
+        solution: Vec<Move>,
+        mut stats: SearchStats,
+        elapsed: Duration,
+        deduplication: Deduplication,
+        strategy: Strategy,
+    ) -> SolveResult {
+        let depth = match outcome {
+            Outcome::Solved => solution.len() as u32,
+            _ => stats.max_depth,
+        };
+        stats.refresh(elapsed, depth);
+        info!(
+            "Seed {}: {:?} after {} expanded states, {} duplicates, {} dead ends and {} deadlocks in {}ms",
+            self.deal.seed,
+            outcome,
+            stats.expanded_nodes,
+            stats.duplicates,
+            stats.dead_ends,
+            stats.deadlocks,
+            stats.elapsed_ms
+        );
+        if outcome == Outcome::Solved && log_enabled!(Level::Debug) {
+            let moves = solution.iter().map(Move::to_string);
+            debug!("Solution: {}", moves.collect::<Vec<_>>().join(" "));
+        }
+        SolveResult {
+            version: RESULT_SCHEMA_VERSION,
+            rules: self.original_game.rules,
+            budget: self.budget,
+            move_generation: self.move_generation,
+            deduplication,
+            strategy,
+            relaxation: None,
+            outcome,
+            stats,
+            solution,
+            deal: self.deal,
+            error: None,
+        }
+    }
+}
+
+// The states a search has expanded
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum VisitedStates {
+    Exact(HashSet<GameCompact>),
+    // Takes much less memory, but may mistake a new state for a visited one and prune it,
+    // which can hide every win of a deal
+    Approximate(BloomFilter),
+}
+
+impl VisitedStates {
+    fn insert(&mut self, state: GameCompact) {
+        match self {
+            VisitedStates::Exact(states) => {
+                states.insert(state);
+            }
+            VisitedStates::Approximate(filter) => filter.insert(&state.data),
+        }
+    }
+
+    fn contains(&self, state: &GameCompact) -> bool {
+        match self {
+            VisitedStates::Exact(states) => states.contains(state),
+            VisitedStates::Approximate(filter) => filter.contains(&state.data),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            VisitedStates::Exact(states) => states.len(),
+            VisitedStates::Approximate(filter) => filter.len(),
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            VisitedStates::Exact(states) => states.clear(),
+            VisitedStates::Approximate(filter) => filter.clear(),
+        }
+    }
+
+    pub fn deduplication(&self) -> Deduplication {
+        match self {
+            VisitedStates::Exact(_) => Deduplication::Exact,
+            VisitedStates::Approximate(filter) => Deduplication::Bloom {
+                memory_bytes: filter.memory_bytes(),
+            },
+        }
+    }
+}
+
+pub type ProgressCallback = Box<dyn FnMut(&SearchStats) + Send>;
+
 pub struct Solver {
-    #[serde(skip_serializing)]
-    original_game: Game,
-    pub random_seed: u32,
-    #[serde(skip_serializing)]
-    visited_games_states: HashSet<GameCompact>,
-    #[serde(skip_serializing)]
-    states_to_visit: Vec<(u32, Game)>,
-    moves_made: Vec<Move>,
-    #[serde(skip_serializing)]
-    culled_state_count: usize,
-    #[serde(skip_serializing)]
-    game_overs_reached: usize,
+    config: SearchConfig,
+    visited_games_states: VisitedStates,
+    strategy: Strategy,
+    // The states reached but not expanded yet, in the order `strategy` expands them
+    frontier: Box<dyn SearchStrategy>,
+    stats: SearchStats,
+    // Relaxed rules to search the deal under first, and the states that search may expand
+    relaxed_check: Option<(Relaxation, u64)>,
+    // Called with the current stats every `progress_interval` expanded states
+    progress: Option<(u64, ProgressCallback)>,
+    // File the search is saved to, and how often
+    checkpoint: Option<(PathBuf, Duration)>,
+}
+
+// Bump whenever the layout of `Checkpoint` or of the types it holds changes
+pub const CHECKPOINT_VERSION: u32 = 7;
+
+// Everything a search needs to carry on where it stopped. Borrowed from the solver when
+// saving, owned when loading.
+#[derive(Serialize, Deserialize)]
+struct Checkpoint<'a> {
+    version: u32,
+    deal: Cow<'a, Deal>,
+    rules: Rules,
+    move_generation: MoveGeneration,
+    prune_deadlocks: bool,
+    strategy: Strategy,
+    stats: SearchStats,
+    // The moves leading to the states to visit, each with the index of the move before it.
+    // A move shared by several paths is saved once, always after the move before it.
+    paths: Vec<(Move, Option<u32>)>,
+    // Depth, state and index of its last move in `paths`
+    states_to_visit: Vec<(u32, Cow<'a, Game>, Option<u32>)>,
+    visited_games_states: Cow<'a, VisitedStates>,
+}
+
+// Index of `path` in `paths`, adding the moves of the path that aren't there yet
+fn save_path(
+    path: &strategy::Path,
+    ids: &mut HashMap<*const PathNode, u32>,
+    paths: &mut Vec<(Move, Option<u32>)>,
+) -> Option<u32> {
+    let mut unsaved = Vec::new();
+    let mut current = path;
+    let mut parent = loop {
+        match current {
+            None => break None,
+            Some(node) => match ids.get(&Arc::as_ptr(node)) {
+                Some(id) => break Some(*id),
+                None => {
+                    unsaved.push(node);
+                    current = &node.parent;
+                }
+            },
+        }
+    };
+    for node in unsaved.into_iter().rev() {
+        paths.push((node.mv, parent));
+        let id = paths.len() as u32 - 1;
+        ids.insert(Arc::as_ptr(node), id);
+        parent = Some(id);
+    }
+    parent
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    Encoding(bincode::Error),
+    UnsupportedVersion(u32),
+    // The strategy keeps state that a checkpoint doesn't save, see `Strategy::is_resumable`
+    UnsupportedStrategy(Strategy),
+    // The saved cards don't make a deck
+    InvalidDeal(InvalidState),
+    // A saved path refers to a move that is not among those saved before it
+    UnknownPath(u32),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckpointError::Io(err) => write!(f, "could not access checkpoint: {}", err),
+            CheckpointError::Encoding(err) => write!(f, "could not encode checkpoint: {}", err),
+            CheckpointError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported checkpoint version {}, expected {}",
+                version, CHECKPOINT_VERSION
+            ),
+            CheckpointError::UnsupportedStrategy(strategy) => {
+                write!(f, "a {} search cannot be saved to a checkpoint", strategy)
+            }
+            CheckpointError::InvalidDeal(err) => write!(f, "invalid deal in checkpoint: {}", err),
+            CheckpointError::UnknownPath(id) => {
+                write!(f, "checkpoint refers to unknown path {}", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+impl From<io::Error> for CheckpointError {
+    fn from(err: io::Error) -> Self {
+        CheckpointError::Io(err)
+    }
+}
+
+impl From<bincode::Error> for CheckpointError {
+    fn from(err: bincode::Error) -> Self {
+        CheckpointError::Encoding(err)
+    }
 }
 
 impl Game {
@@ -33,7 +475,7 @@ impl Game {
         let mut data = [u8::MAX; 52 + 4];
 
         fn set_highest_bit(bit: &mut u8) {
-            *bit = *bit | (1 << 7);
+            *bit |= 1 << 7;
         }
 
         // First 4 bytes are the foundations
@@ -69,25 +511,202 @@ impl Game {
 }
 
 impl Solver {
-    pub fn new() -> Self {
-        let original_game = Game::new();
-        let mut visited_games_states = HashSet::new();
-        visited_games_states.insert(original_game.compact_state());
-        let mut states_to_visit = Vec::new();
-        let valid_moves = original_game.valid_moves();
-        for valid_move in &valid_moves {
-            states_to_visit.push((1, original_game.handle_move(valid_move)));
-        }
-        let random_seed = original_game.random_seed;
+    pub fn new(config: SearchConfig) -> Self {
+        let strategy = Strategy::default();
+        let mut frontier = strategy.frontier();
+        frontier.push(SearchNode::root(config.original_game.clone()));
         Self {
-            original_game,
-            random_seed,
-            visited_games_states,
+            config,
+            visited_games_states: VisitedStates::Exact(HashSet::new()),
+            strategy,
+            frontier,
+            stats: SearchStats::default(),
+            relaxed_check: None,
+            progress: None,
+            checkpoint: None,
+        }
+    }
+
+    // A search of `deal` with the default settings
+    pub fn from_deal(deal: Deal, rules: Rules) -> Self {
+        Self::new(SearchConfig::from_deal(deal, rules))
+    }
+
+    // Resumes the search saved in a checkpoint file. The budget covers the whole search,
+    // the time and states spent before the checkpoint included. The other settings are
+    // those the search was started with.
+    pub fn from_checkpoint(
+        path: impl AsRef<Path>,
+        budget: Budget,
+        cancellation: Option<CancellationToken>,
+    ) -> Result<Self, CheckpointError> {
+        let checkpoint: Checkpoint = bincode::deserialize_from(BufReader::new(File::open(path)?))?;
+        if checkpoint.version != CHECKPOINT_VERSION {
+            return Err(CheckpointError::UnsupportedVersion(checkpoint.version));
+        }
+        let deal = checkpoint.deal.into_owned();
+        Game::try_from_deal(&deal, checkpoint.rules).map_err(CheckpointError::InvalidDeal)?;
+        let config = SearchConfig {
+            budget,
+            cancellation,
+            ..SearchConfig::from_deal(deal, checkpoint.rules)
+                .with_move_generation(checkpoint.move_generation)
+                .with_deadlock_pruning(checkpoint.prune_deadlocks)
+        };
+        let mut solver = Self::new(config);
+        solver.stats = checkpoint.stats;
+        solver.strategy = checkpoint.strategy;
+        solver.frontier = checkpoint.strategy.frontier();
+        let mut paths: Vec<Arc<PathNode>> = Vec::with_capacity(checkpoint.paths.len());
+        // A path is always saved after the one it extends, so only those before can be found
+        let find = |paths: &[Arc<PathNode>], id: Option<u32>| {
+            id.map(|id| {
+                paths
+                    .get(id as usize)
+                    .cloned()
+                    .ok_or(CheckpointError::UnknownPath(id))
+            })
+            .transpose()
+        };
+        for (mv, parent) in checkpoint.paths {
+            let parent = find(&paths, parent)?;
+            paths.push(Arc::new(PathNode { mv, parent }));
+        }
+        for (depth, game, path) in checkpoint.states_to_visit {
+            solver.frontier.push(SearchNode {
+                depth,
+                game: game.into_owned(),
+                path: find(&paths, path)?,
+            });
+        }
+        solver.visited_games_states = checkpoint.visited_games_states.into_owned();
+        Ok(solver)
+    }
+
+    // Saves the search to `path` every `interval`, and when it stops without deciding the
+    // deal, so that `from_checkpoint` can pick it up later
+    pub fn with_checkpoint(mut self, path: impl Into<PathBuf>, interval: Duration) -> Self {
+        self.checkpoint = Some((path.into(), interval));
+        self
+    }
+
+    // Writes to a temporary file first, so an interruption never leaves a truncated checkpoint
+    pub fn save_checkpoint(&self, path: &Path) -> Result<(), CheckpointError> {
+        if !self.strategy.is_resumable() {
+            return Err(CheckpointError::UnsupportedStrategy(self.strategy));
+        }
+        let mut ids = HashMap::new();
+        let mut paths = Vec::new();
+        let states_to_visit = self
+            .frontier
+            .nodes()
+            .into_iter()
+            .map(|node| {
+                let path = save_path(&node.path, &mut ids, &mut paths);
+                (node.depth, Cow::Borrowed(&node.game), path)
+            })
+            .collect();
+        let checkpoint = Checkpoint {
+            version: CHECKPOINT_VERSION,
+            deal: Cow::Borrowed(&self.config.deal),
+            rules: self.config.original_game.rules,
+            move_generation: self.config.move_generation,
+            prune_deadlocks: self.config.prune_deadlocks,
+            strategy: self.strategy,
+            stats: self.stats,
+            paths,
             states_to_visit,
-            moves_made: Vec::new(),
-            culled_state_count: 0,
-            game_overs_reached: 0,
+            visited_games_states: Cow::Borrowed(&self.visited_games_states),
+        };
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        bincode::serialize_into(&mut writer, &checkpoint)?;
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    // Saves the search if a checkpoint file was given, logging rather than failing the search
+    fn checkpoint(&mut self, elapsed: Duration) {
+        if let Some((path, _)) = &self.checkpoint {
+            self.stats.refresh(elapsed, self.stats.max_depth);
+            match self.save_checkpoint(path) {
+                Ok(()) => info!(
+                    "Seed {}: saved checkpoint after {} expanded states to {}",
+                    self.config.deal.seed,
+                    self.stats.expanded_nodes,
+                    path.display()
+                ),
+                Err(err) => error!("Seed {}: {}", self.config.deal.seed, err),
+            }
+        }
+    }
+
+    pub fn config(&self) -> &SearchConfig {
+        &self.config
+    }
+
+    // Expands states in the order of `strategy`, carrying over the states already reached
+    pub fn with_strategy(mut self, strategy: Strategy) -> Self {
+        let mut frontier = strategy.frontier();
+        for node in self.frontier.nodes() {
+            frontier.push(node.clone());
+        }
+        self.strategy = strategy;
+        self.frontier = frontier;
+        self
+    }
+
+    // Keeps the visited states in a Bloom filter of about `memory_bytes` instead of a hash
+    // set. The search then fits many more states in the same memory, but an `Unsolvable`
+    // outcome is no longer a proof. Solutions are still valid.
+    pub fn with_bloom_filter(mut self, memory_bytes: usize) -> Self {
+        let mut filter = BloomFilter::with_memory(memory_bytes);
+        if let VisitedStates::Exact(states) = &self.visited_games_states {
+            states.iter().for_each(|state| filter.insert(&state.data));
         }
+        self.visited_games_states = VisitedStates::Approximate(filter);
+        self
+    }
+
+    // Searches the deal under `relaxation` before the real search, expanding at most
+    // `max_nodes` states. Every move of the real game is legal in the relaxed one, so a deal
+    // the relaxed search exhausts is proven unsolvable. Otherwise the real search runs as
+    // usual. Most relaxed deals can be won, even among the unsolvable ones, so the check only
+    // pays off on deals hopeless from the start, which it settles within a few states.
+    pub fn with_relaxed_check(mut self, relaxation: Relaxation, max_nodes: u64) -> Self {
+        self.relaxed_check = Some((relaxation, max_nodes));
+        self
+    }
+
+    // The search of the relaxed check, complete and exact so that running out of states is a
+    // proof
+    fn relaxed_solver(&self, relaxation: Relaxation, max_nodes: u64) -> Solver {
+        let rules = Rules {
+            relaxation,
+            ..self.config.original_game.rules
+        };
+        Solver::new(SearchConfig {
+            budget: Budget {
+                time_limit_ms: self.config.budget.time_limit_ms,
+                max_nodes: Some(max_nodes),
+            },
+            move_generation: MoveGeneration::Complete,
+            prune_deadlocks: self.config.prune_deadlocks,
+            cancellation: self.config.cancellation.clone(),
+            ..SearchConfig::from_deal(self.config.deal.clone(), rules)
+        })
+    }
+
+    pub fn with_progress(
+        mut self,
+        interval: u64,
+        callback: impl FnMut(&SearchStats) + Send + 'static,
+    ) -> Self {
+        self.progress = Some((interval.max(1), Box::new(callback)));
+        self
     }
 
     pub fn is_game_lost(valid_moves: &HashSet<Move>) -> bool {
@@ -101,80 +720,382 @@ impl Solver {
         }
     }
 
-    pub fn log_state(&self, new_state: &Game, print_moves: bool) {
-        if VERBOSE_PRINT {
-            println!("\nOriginal Game:\n{}", self.original_game);
-            println!("\nCurrent State:\n{}", new_state);
-        }
-        println!(
-            "States Visited: {}, States to Visit: {}, Culled States: {}, Game Overs: {}",
-            self.visited_games_states.len(),
-            self.states_to_visit.len(),
-            self.culled_state_count,
-            self.game_overs_reached,
-        );
-        if VERBOSE_PRINT && print_moves {
-            println!("Move Made:");
-            for mv in &self.moves_made {
-                println!("{:?}", mv);
-            }
-        }
+    fn result(self, outcome: Outcome, solution: Vec<Move>, elapsed: Duration) -> SolveResult {
+        let deduplication = self.visited_games_states.deduplication();
+        self.config.result(
+            outcome,
+            solution,
+            self.stats,
+            elapsed,
+            deduplication,
+            self.strategy,
+        )
     }
 
-    pub fn original_stock(&self) -> &[Card] {
-        &self.original_game.stock.0
-    }
-    
-    pub fn log_original_state(&self) {
-        println!("{}", self.original_game);
+    fn error_result(
+        self,
+        mv: &Move,
+        state: &Game,
+        err: MoveError,
+        elapsed: Duration,
+    ) -> SolveResult {
+        error!(
+            "Seed {}: {} failed: {}\n{}",
+            self.config.deal.seed, mv, err, state
+        );
+        let error = format!("{} failed: {}", mv, err);
+        SolveResult {
+            error: Some(error),
+            ..self.result(Outcome::Error, Vec::new(), elapsed)
+        }
     }
 
-    pub fn is_solvable(mut self) -> Option<Self> {
-        let cutoff_time = 5000.0;
+    pub fn solve(mut self) -> SolveResult {
+        // Time spent before the checkpoint this search was resumed from, if any
+        let earlier = Duration::from_millis(self.stats.elapsed_ms);
         let timer = Instant::now();
-        let mut current_depth = 0;
-        while !self.states_to_visit.is_empty() {
-            let (new_depth, new_state) = self.states_to_visit.pop().unwrap();
-            if new_depth > current_depth {
-                self.moves_made.push(new_state.prev_move.unwrap());
-            } else {
-                let depth_diff = current_depth - new_depth;
-                for _ in 0..depth_diff + 1 {
-                    self.moves_made.pop();
-                }
+        let elapsed = || earlier + timer.elapsed();
+        let mut last_checkpoint = Instant::now();
+        if let Some((relaxation, max_nodes)) = self.relaxed_check {
+            let relaxed = self.relaxed_solver(relaxation, max_nodes).solve();
+            debug!(
+                "Seed {}: {:?} with relaxed {} after {} expanded states",
+                self.config.deal.seed, relaxed.outcome, relaxation, relaxed.stats.expanded_nodes
+            );
+            if relaxed.outcome == Outcome::Unsolvable {
+                self.stats = relaxed.stats;
+                return SolveResult {
+                    relaxation: Some(relaxation),
+                    ..self.result(Outcome::Unsolvable, Vec::new(), elapsed())
+                };
             }
-            current_depth = new_depth;
-            if VERBOSE_PRINT {
-                self.log_state(&new_state, false);
+        }
+        debug!(
+            "Solving seed {} with {}:\n{}",
+            self.config.deal.seed, self.strategy, self.config.original_game
+        );
+        loop {
+            let Some(node) = self.frontier.pop() else {
+                if self.frontier.restart() {
+                    debug!(
+                        "Seed {}: restarting after {} expanded states",
+                        self.config.deal.seed, self.stats.expanded_nodes
+                    );
+                    self.visited_games_states.clear();
+                    self.frontier
+                        .push(SearchNode::root(self.config.original_game.clone()));
+                    continue;
+                }
+                break;
+            };
+            trace!(
+                "Depth {}, frontier {}, visited {}:\n{:#}",
+                node.depth,
+                self.frontier.len(),
+                self.visited_games_states.len(),
+                node.game
+            );
+            if node.game.is_game_won() {
+                let solution = node.moves();
+                return self.result(Outcome::Solved, solution, elapsed());
             }
-            if new_state.is_game_won() {
-                self.log_state(&new_state, true);
-                return Some(self);
+            // A state can be reached again before it is expanded, and so be in the frontier
+            // twice. Only the first one out is expanded.
+            let state = node.game.compact_state();
+            if self.visited_games_states.contains(&state) {
+                self.stats.duplicates += 1;
+                continue;
             }
-            self.visited_games_states.insert(new_state.compact_state());
-            let valid_moves = new_state.valid_moves();
-            if !Self::is_game_lost(&valid_moves) {
+            self.visited_games_states.insert(state);
+            self.stats.expanded_nodes += 1;
+            self.stats.max_depth = self.stats.max_depth.max(node.depth);
+            let valid_moves = node.game.moves(self.config.move_generation);
+            if Self::is_game_lost(&valid_moves) {
+                self.stats.dead_ends += 1;
+            } else if self.config.prune_deadlocks && node.game.is_deadlocked() {
+                self.stats.deadlocks += 1;
+            } else {
+                // Moves come out of a hash set in a different order every run. Sorting them
+                // makes the search, and so its outcome within a budget, the same every time.
+                let mut valid_moves = valid_moves.into_iter().collect::<Vec<_>>();
+                valid_moves.sort_unstable();
                 for valid_move in &valid_moves {
-                    let new_state_to_visit = new_state.handle_move(valid_move);
+                    let next_game = match node.game.handle_move(valid_move) {
+                        Ok(next_game) => next_game,
+                        Err(err) => {
+                            return self.error_result(valid_move, &node.game, err, elapsed())
+                        }
+                    };
+                    self.stats.generated_nodes += 1;
                     if !self
                         .visited_games_states
-                        .contains(&new_state_to_visit.compact_state())
+                        .contains(&next_game.compact_state())
                     {
-                        self.states_to_visit
-                            .push((current_depth + 1, new_state_to_visit));
+                        self.frontier.push(node.child(*valid_move, next_game));
                     } else {
-                        self.culled_state_count += 1;
+                        self.stats.duplicates += 1;
                     }
                 }
-            } else {
-                self.game_overs_reached += 1;
             }
-            let elapsed_time = timer.elapsed().as_millis() as f64;
-            if elapsed_time >= cutoff_time {
-                // Hacky return when it takes too long for now
-                return None;
+            self.stats.peak_frontier_size = self.stats.peak_frontier_size.max(self.frontier.len());
+            self.stats.peak_visited_size = self
+                .stats
+                .peak_visited_size
+                .max(self.visited_games_states.len());
+            if self
+                .config
+                .is_out_of_budget(self.stats.expanded_nodes, elapsed())
+            {
+                self.checkpoint(elapsed());
+                return self.result(Outcome::Timeout, Vec::new(), elapsed());
             }
+            if let Some((interval, callback)) = &mut self.progress {
+                if self.stats.expanded_nodes.is_multiple_of(*interval) {
+                    self.stats.refresh(elapsed(), self.stats.max_depth);
+                    callback(&self.stats);
+                }
+            }
+            if self.config.is_cancelled() {
+                self.checkpoint(elapsed());
+                return self.result(Outcome::Cancelled, Vec::new(), elapsed());
+            }
+            if let Some((_, interval)) = &self.checkpoint {
+                if last_checkpoint.elapsed() >= *interval {
+                    self.checkpoint(elapsed());
+                    last_checkpoint = Instant::now();
+                }
+            }
+        }
+        self.result(Outcome::Unsolvable, Vec::new(), elapsed())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // The game `moves_left` moves away from the end of the solution of seed 0
+    pub(crate) fn near_the_end(moves_left: usize) -> Game {
+        let known = Solver::from_deal(Deal::from_seed(0), Rules::default()).solve();
+        assert_eq!(known.outcome, Outcome::Solved);
+        let start = known.solution.len() - moves_left;
+        known.solution[..start]
+            .iter()
+            .try_fold(known.game(), |game, mv| game.handle_move(mv))
+            .unwrap()
+    }
+
+    // A solver starting from `near_the_end`
+    fn solver_near_the_end(moves_left: usize, strategy: Strategy) -> Solver {
+        let mid_game = near_the_end(moves_left);
+        let mut solver = Solver::from_deal(Deal::from_seed(0), Rules::default());
+        solver.strategy = strategy;
+        solver.frontier = strategy.frontier();
+        solver.frontier.push(SearchNode::root(mid_game.clone()));
+        solver.config.original_game = mid_game;
+        solver
+    }
+
+    #[test]
+    fn shortest_solutions_from_breadth_first_and_iterative_deepening() {
+        let dfs = solver_near_the_end(12, Strategy::Dfs).solve();
+        assert_eq!(dfs.outcome, Outcome::Solved);
+        for strategy in [Strategy::Bfs, Strategy::IterativeDeepening] {
+            let solver = solver_near_the_end(12, strategy);
+            let mid_game = solver.config.original_game.clone();
+            let result = solver.solve();
+            assert_eq!(result.outcome, Outcome::Solved, "{}", strategy);
+            assert!(result.solution.len() <= dfs.solution.len(), "{}", strategy);
+            let end = result
+                .solution
+                .iter()
+                .try_fold(mid_game, |game, mv| game.handle_move(mv))
+                .unwrap();
+            assert!(end.is_game_won(), "{}", strategy);
+        }
+    }
+
+    #[test]
+    fn resumed_breadth_first_search_matches_uninterrupted_search() {
+        let path = std::env::temp_dir().join(format!("bfs-{}.bin", std::process::id()));
+        let uninterrupted = solver_near_the_end(12, Strategy::Bfs).solve();
+        let mut first = solver_near_the_end(12, Strategy::Bfs);
+        first.config.budget = Budget::nodes(uninterrupted.stats.expanded_nodes / 2);
+        let first = first.with_checkpoint(&path, Duration::MAX).solve();
+        assert_eq!(first.outcome, Outcome::Timeout);
+        let second = Solver::from_checkpoint(&path, Budget::unlimited(), None)
+            .unwrap()
+            .solve();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(second.outcome, Outcome::Solved);
+        assert_eq!(second.solution, uninterrupted.solution);
+        assert_eq!(
+            second.stats.expanded_nodes,
+            uninterrupted.stats.expanded_nodes
+        );
+    }
+
+    // Saves the search of `solver_near_the_end` with its checkpoint changed by `corrupt`, and loads
+    // it back
+    fn load_corrupted(
+        name: &str,
+        corrupt: impl FnOnce(&mut Checkpoint),
+    ) -> Result<Solver, CheckpointError> {
+        let path = std::env::temp_dir().join(format!("{}-{}.bin", name, std::process::id()));
+        let solver = solver_near_the_end(12, Strategy::Bfs);
+        let mut checkpoint = Checkpoint {
+            version: CHECKPOINT_VERSION,
+            deal: Cow::Borrowed(&solver.config.deal),
+            rules: solver.config.original_game.rules,
+            move_generation: solver.config.move_generation,
+            prune_deadlocks: solver.config.prune_deadlocks,
+            strategy: solver.strategy,
+            stats: solver.stats,
+            paths: vec![("S".parse::<Move>().unwrap(), None)],
+            states_to_visit: vec![(1, Cow::Borrowed(&solver.config.original_game), Some(0))],
+            visited_games_states: Cow::Borrowed(&solver.visited_games_states),
+        };
+        corrupt(&mut checkpoint);
+        fs::write(&path, bincode::serialize(&checkpoint).unwrap()).unwrap();
+        let loaded = Solver::from_checkpoint(&path, Budget::default(), None);
+        fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    #[test]
+    fn corrupt_checkpoints_are_rejected() {
+        assert!(load_corrupted("sound", |_| ()).is_ok());
+        let short_deck = load_corrupted("short-deck", |checkpoint| {
+            checkpoint.deal.to_mut().cards.pop();
+        });
+        assert!(matches!(short_deck, Err(CheckpointError::InvalidDeal(_))));
+        let duplicate = load_corrupted("duplicate-card", |checkpoint| {
+            let cards = &mut checkpoint.deal.to_mut().cards;
+            cards[0] = cards[1];
+        });
+        assert!(matches!(duplicate, Err(CheckpointError::InvalidDeal(_))));
+        let unknown_parent = load_corrupted("unknown-parent", |checkpoint| {
+            checkpoint
+                .paths
+                .push(("S".parse::<Move>().unwrap(), Some(5)));
+        });
+        assert!(matches!(
+            unknown_parent,
+            Err(CheckpointError::UnknownPath(5))
+        ));
+        let unknown_state_path = load_corrupted("unknown-state-path", |checkpoint| {
+            checkpoint.states_to_visit[0].2 = Some(1);
+        });
+        assert!(matches!(
+            unknown_state_path,
+            Err(CheckpointError::UnknownPath(1))
+        ));
+    }
+
+    fn budget(time_limit_ms: u64, max_nodes: u64) -> Budget {
+        Budget {
+            time_limit_ms,
+            max_nodes: Some(max_nodes),
         }
-        None
+    }
+
+    #[test]
+    fn effective_branching_factor_fits_a_uniform_tree() {
+        let close = |nodes, depth, expected: f64| {
+            let branching = effective_branching_factor(nodes, depth);
+            assert!(
+                (branching - expected).abs() < 1e-9,
+                "{} nodes at depth {}: {}",
+                nodes,
+                depth,
+                branching
+            );
+        };
+        // 2 + 4 + 8 and 3 + 9 + 27 + 81
+        close(14, 3, 2.0);
+        close(120, 4, 3.0);
+        close(52, 1, 52.0);
+        // A single path, and less than one
+        close(10, 10, 1.0);
+        close(0, 10, 0.0);
+        close(10, 0, 0.0);
+    }
+
+    #[test]
+    fn solved_stats_use_the_depth_of_the_solution() {
+        let result = Solver::from_deal(Deal::from_seed(1), Rules::default()).solve();
+        assert_eq!(result.outcome, Outcome::Solved);
+        let depth = result.solution.len() as u32;
+        assert_eq!(
+            result.stats.effective_branching_factor,
+            effective_branching_factor(result.stats.expanded_nodes, depth)
+        );
+        assert!(result.stats.effective_branching_factor > 1.0);
+    }
+
+    #[test]
+    fn scaled_budget_grows_up_to_the_cap() {
+        let cap = budget(10_000, 1_000_000);
+        assert_eq!(budget(1000, 1000).scaled(4.0, &cap), budget(4000, 4000));
+        assert_eq!(
+            budget(4000, 400_000).scaled(4.0, &cap),
+            budget(10_000, 1_000_000)
+        );
+        // No node limit stays unlimited
+        let unlimited = Budget {
+            time_limit_ms: 1000,
+            max_nodes: None,
+        };
+        assert_eq!(unlimited.scaled(2.0, &cap).max_nodes, None);
+    }
+
+    #[test]
+    fn scaled_budget_past_the_cap_never_shrinks() {
+        let start = budget(20_000, 2_000_000);
+        assert_eq!(start.scaled(4.0, &budget(10_000, 1_000_000)), start);
+    }
+
+    // A deal that takes far more than the states the tests below let it expand
+    const HARD_SEED: u32 = 1268242272;
+
+    fn hard_config(max_nodes: u64) -> SearchConfig {
+        SearchConfig::from_deal(Deal::from_seed(HARD_SEED), Rules::default())
+            .with_budget(Budget::nodes(max_nodes))
+    }
+
+    #[test]
+    fn cancelled_search_stops_with_cancelled() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let result = Solver::new(hard_config(u64::MAX).with_cancellation(token)).solve();
+        assert_eq!(result.outcome, Outcome::Cancelled);
+        assert_eq!(result.stats.expanded_nodes, 1);
+    }
+
+    #[test]
+    fn search_cancelled_midway_stops_there() {
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        let result = Solver::new(hard_config(u64::MAX).with_cancellation(token))
+            .with_progress(300, move |_| canceller.cancel())
+            .solve();
+        assert_eq!(result.outcome, Outcome::Cancelled);
+        assert_eq!(result.stats.expanded_nodes, 300);
+    }
+
+    #[test]
+    fn progress_is_reported_every_interval() {
+        let reported = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let reports = reported.clone();
+        let result = Solver::new(hard_config(1000))
+            .with_progress(100, move |stats| {
+                reports.lock().unwrap().push(stats.expanded_nodes)
+            })
+            .solve();
+        assert_eq!(result.outcome, Outcome::Timeout);
+        // The search stops on its budget before reporting the last interval
+        let expected = (1..10).map(|step| step * 100).collect::<Vec<_>>();
+        assert_eq!(*reported.lock().unwrap(), expected);
     }
 }
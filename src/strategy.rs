@@ -0,0 +1,405 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+    fmt, iter,
+    str::FromStr,
+    sync::Arc,
+};
+
+use crate::{card::NUM_CARDS_DECK, moves::Move, Game};
+
+pub const DEFAULT_BEAM_WIDTH: usize = 1000;
+
+// How much deeper each round of an iterative deepening search goes than the one before
+pub const DEEPENING_STEP: u32 = 10;
+
+// The order in which a search expands the states it has reached
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    // Depth-first, the most recently reached state first
+    #[default]
+    Dfs,
+    // Breadth-first, so the first solution found is a shortest one
+    Bfs,
+    // The state closest to a win by `distance_estimate` first
+    BestFirst,
+    // Depth-first down to a depth limit, raised by `DEEPENING_STEP` and searched again from
+    // the start for as long as some state was cut off by the limit
+    IterativeDeepening,
+    // Breadth-first, keeping only the `width` states of each depth closest to a win. The
+    // dropped states are never searched, so running out of states proves nothing.
+    Beam {
+        width: usize,
+    },
+}
+
+impl Strategy {
+    // Whether the search reaches every state it can before giving up on a deal
+    pub fn is_exhaustive(&self) -> bool {
+        !matches!(self, Strategy::Beam { .. })
+    }
+
+    // Whether a search can be saved to a checkpoint and resumed. The others keep state of
+    // their own besides the states to visit.
+    pub fn is_resumable(&self) -> bool {
+        matches!(self, Strategy::Dfs | Strategy::Bfs | Strategy::BestFirst)
+    }
+
+    pub fn frontier(&self) -> Box<dyn SearchStrategy> {
+        match self {
+            Strategy::Dfs => Box::<DepthFirst>::default(),
+            Strategy::Bfs => Box::<BreadthFirst>::default(),
+            Strategy::BestFirst => Box::<BestFirst>::default(),
+            Strategy::IterativeDeepening => Box::new(IterativeDeepening::new(DEEPENING_STEP)),
+            Strategy::Beam { width } => Box::new(Beam::new(*width)),
+        }
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    // `beam` takes an optional width, as in `beam:500`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "dfs" => Ok(Strategy::Dfs),
+            None if s == "bfs" => Ok(Strategy::Bfs),
+            None if s == "best-first" => Ok(Strategy::BestFirst),
+            None if s == "iterative-deepening" => Ok(Strategy::IterativeDeepening),
+            None if s == "beam" => Ok(Strategy::Beam {
+                width: DEFAULT_BEAM_WIDTH,
+            }),
+            Some(("beam", width)) => match width.parse() {
+                Ok(width) if width > 0 => Ok(Strategy::Beam { width }),
+                _ => Err(format!("`{}` is not a positive beam width", width)),
+            },
+            _ => Err(format!(
+                "`{}` is not a search strategy, expected `dfs`, `bfs`, `best-first`, \
+                 `iterative-deepening` or `beam[:<width>]`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Strategy::Dfs => write!(f, "dfs"),
+            Strategy::Bfs => write!(f, "bfs"),
+            Strategy::BestFirst => write!(f, "best-first"),
+            Strategy::IterativeDeepening => write!(f, "iterative-deepening"),
+            Strategy::Beam { width } => write!(f, "beam:{}", width),
+        }
+    }
+}
+
+// The moves that lead to a state, shared between the states of a branch so a state only adds
+// its own move to its parent's path
+pub(crate) struct PathNode {
+    pub(crate) mv: Move,
+    pub(crate) parent: Path,
+}
+
+pub(crate) type Path = Option<Arc<PathNode>>;
+
+// Dropping the last reference to a long branch would otherwise drop its nodes recursively, one
+// stack frame per move, and overflow the stack on deep searches
+impl Drop for PathNode {
+    fn drop(&mut self) {
+        let mut parent = self.parent.take();
+        while let Some(mut node) = parent.and_then(Arc::into_inner) {
+            parent = node.parent.take();
+        }
+    }
+}
+
+pub(crate) fn path_moves(path: &Path) -> Vec<Move> {
+    let mut moves = iter::successors(path.as_deref(), |node| node.parent.as_deref())
+        .map(|node| node.mv)
+        .collect::<Vec<_>>();
+    moves.reverse();
+    moves
+}
+
+// A state reached by the search, waiting to be expanded
+#[derive(Clone)]
+pub struct SearchNode {
+    // Number of moves from the dealt game
+    pub depth: u32,
+    pub game: Game,
+    pub(crate) path: Path,
+}
+
+impl SearchNode {
+    // The dealt game, where every search starts
+    pub fn root(game: Game) -> Self {
+        Self {
+            depth: 0,
+            game,
+            path: None,
+        }
+    }
+
+    // The state reached from this one by `mv`, already played into `game`
+    pub fn child(&self, mv: Move, game: Game) -> Self {
+        Self {
+            depth: self.depth + 1,
+            game,
+            path: Some(Arc::new(PathNode {
+                mv,
+                parent: self.path.clone(),
+            })),
+        }
+    }
+
+    pub fn moves(&self) -> Vec<Move> {
+        path_moves(&self.path)
+    }
+}
+
+// The states a search has reached but not expanded yet, and the order it expands them in.
+// `Solver` does everything else, generating moves, skipping visited states and keeping to
+// the budget, the same way whatever the strategy.
+pub trait SearchStrategy: Send {
+    fn push(&mut self, node: SearchNode);
+
+    // The next state to expand, `None` once there is none left
+    fn pop(&mut self) -> Option<SearchNode>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The states waiting to be expanded, in an order that gives the same frontier when pushed
+    // into a new one. Used to save a search.
+    fn nodes(&self) -> Vec<&SearchNode>;
+
+    // Called once the frontier runs dry. Returns whether the search should start over from
+    // the dealt game with the visited states forgotten, as iterative deepening does.
+    fn restart(&mut self) -> bool {
+        false
+    }
+}
+
+// Rough estimate of the moves left to win: every card still has to reach a foundation, and
+// a card locked under others first waits for them to move, counted as two more moves
+pub fn distance_estimate(game: &Game) -> u32 {
+    let on_foundations = game.foundation_stack.count_ones();
+    let locked = game
+        .tableaus
+        .iter()
+        .zip(&game.first_unlocked_idx)
+        .filter(|(tableau, _)| !tableau.0.is_empty())
+        .map(|(_, idx)| *idx as u32)
+        .sum::<u32>();
+    NUM_CARDS_DECK as u32 - on_foundations + 2 * locked
+}
+
+#[derive(Default)]
+pub struct DepthFirst {
+    stack: Vec<SearchNode>,
+}
+
+impl SearchStrategy for DepthFirst {
+    fn push(&mut self, node: SearchNode) {
+        self.stack.push(node);
+    }
+
+    fn pop(&mut self) -> Option<SearchNode> {
+        self.stack.pop()
+    }
+
+    fn len(&self) -> usize {
+        self.stack.len()
+    }
+
+    fn nodes(&self) -> Vec<&SearchNode> {
+        self.stack.iter().collect()
+    }
+}
+
+#[derive(Default)]
+pub struct BreadthFirst {
+    queue: VecDeque<SearchNode>,
+}
+
+impl SearchStrategy for BreadthFirst {
+    fn push(&mut self, node: SearchNode) {
+        self.queue.push_back(node);
+    }
+
+    fn pop(&mut self) -> Option<SearchNode> {
+        self.queue.pop_front()
+    }
+
+    fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    fn nodes(&self) -> Vec<&SearchNode> {
+        self.queue.iter().collect()
+    }
+}
+
+// A node ordered by its estimate, ties going to the most recently pushed so that the search
+// dives like a depth-first one between equally promising states
+struct Prioritized {
+    estimate: Reverse<u32>,
+    sequence: u64,
+    node: SearchNode,
+}
+
+impl PartialEq for Prioritized {
+    fn eq(&self, other: &Self) -> bool {
+        (self.estimate, self.sequence) == (other.estimate, other.sequence)
+    }
+}
+
+impl Eq for Prioritized {}
+
+impl PartialOrd for Prioritized {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Prioritized {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.estimate, self.sequence).cmp(&(other.estimate, other.sequence))
+    }
+}
+
+#[derive(Default)]
+pub struct BestFirst {
+    heap: BinaryHeap<Prioritized>,
+    pushed: u64,
+}
+
+impl SearchStrategy for BestFirst {
+    fn push(&mut self, node: SearchNode) {
+        self.heap.push(Prioritized {
+            estimate: Reverse(distance_estimate(&node.game)),
+            sequence: self.pushed,
+            node,
+        });
+        self.pushed += 1;
+    }
+
+    fn pop(&mut self) -> Option<SearchNode> {
+        self.heap.pop().map(|prioritized| prioritized.node)
+    }
+
+    fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    fn nodes(&self) -> Vec<&SearchNode> {
+        let mut nodes = self.heap.iter().collect::<Vec<_>>();
+        nodes.sort_by_key(|prioritized| prioritized.sequence);
+        nodes
+            .into_iter()
+            .map(|prioritized| &prioritized.node)
+            .collect()
+    }
+}
+
+pub struct IterativeDeepening {
+    stack: Vec<SearchNode>,
+    step: u32,
+    limit: u32,
+    // Whether a state deeper than the limit was dropped this round
+    cut_off: bool,
+}
+
+impl IterativeDeepening {
+    pub fn new(step: u32) -> Self {
+        Self {
+            stack: Vec::new(),
+            step,
+            limit: step,
+            cut_off: false,
+        }
+    }
+}
+
+impl SearchStrategy for IterativeDeepening {
+    fn push(&mut self, node: SearchNode) {
+        if node.depth > self.limit {
+            self.cut_off = true;
+        } else {
+            self.stack.push(node);
+        }
+    }
+
+    fn pop(&mut self) -> Option<SearchNode> {
+        self.stack.pop()
+    }
+
+    fn len(&self) -> usize {
+        self.stack.len()
+    }
+
+    fn nodes(&self) -> Vec<&SearchNode> {
+        self.stack.iter().collect()
+    }
+
+    // A round that cut nothing off searched every state, so a deeper one would find no more
+    fn restart(&mut self) -> bool {
+        if self.cut_off {
+            self.limit += self.step;
+            self.cut_off = false;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+pub struct Beam {
+    width: usize,
+    // States of the depth being expanded, the most promising last
+    layer: Vec<SearchNode>,
+    // Their successors, one move deeper
+    next_layer: Vec<SearchNode>,
+}
+
+impl Beam {
+    pub fn new(width: usize) -> Self {
+        Self {
+            width: width.max(1),
+            layer: Vec::new(),
+            next_layer: Vec::new(),
+        }
+    }
+}
+
+impl SearchStrategy for Beam {
+    fn push(&mut self, node: SearchNode) {
+        self.next_layer.push(node);
+    }
+
+    fn pop(&mut self) -> Option<SearchNode> {
+        if self.layer.is_empty() {
+            // Stable, so that equally promising states keep the order they were reached in
+            self.next_layer
+                .sort_by_key(|node| distance_estimate(&node.game));
+            self.next_layer.truncate(self.width);
+            self.next_layer.reverse();
+            std::mem::swap(&mut self.layer, &mut self.next_layer);
+        }
+        self.layer.pop()
+    }
+
+    fn len(&self) -> usize {
+        self.layer.len() + self.next_layer.len()
+    }
+
+    fn nodes(&self) -> Vec<&SearchNode> {
+        self.layer.iter().rev().chain(&self.next_layer).collect()
+    }
+}
@@ -0,0 +1,284 @@
+use std::fmt;
+
+use crate::{card::*, Game};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    // A card in the stock, waste or a tableau whose index is past the end of the deck
+    UnknownCard(Card),
+    // A card in the stock or waste marked as face down, which only tableau cards can be
+    FaceDownOutsideTableau(Card),
+    // A card that is nowhere in the game
+    MissingCard(Card),
+    // A card found in more than one place among the stock, waste and tableaus
+    DuplicateCard {
+        card: Card,
+        count: u8,
+    },
+    // A card in the stock, waste or a tableau that is also marked as played to a foundation
+    CardInFoundationAndPile(Card),
+    // A foundation topped by a card of another suit
+    WrongFoundationSuit {
+        foundation: u8,
+        card: Card,
+    },
+    // The cards marked as played to a foundation don't run from the ace to its top card
+    InconsistentFoundationStack {
+        foundation: u8,
+        expected: u64,
+        found: u64,
+    },
+    // `first_unlocked_idx` doesn't match what the cards of the tableau give
+    StaleUnlockedIndex {
+        tableau: u8,
+        expected: u8,
+        found: u8,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cards = |mask: u64| {
+            (0..NUM_CARDS_DECK)
+                .filter(|card| mask & (1 << card) != 0)
+                .map(card_name)
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        match self {
+            Violation::UnknownCard(card) => write!(f, "unknown card {}", card),
+            Violation::FaceDownOutsideTableau(card) => write!(
+                f,
+                "card {} is face down in the stock or waste",
+                card_name(*card)
+            ),
+            Violation::MissingCard(card) => write!(f, "missing card {}", card_name(*card)),
+            Violation::DuplicateCard { card, count } => {
+                write!(f, "card {} appears {} times", card_name(*card), count)
+            }
+            Violation::CardInFoundationAndPile(card) => write!(
+                f,
+                "card {} is both in play and on a foundation",
+                card_name(*card)
+            ),
+            Violation::WrongFoundationSuit { foundation, card } => write!(
+                f,
+                "foundation {} is topped by {} of another suit",
+                foundation,
+                card_name(*card)
+            ),
+            Violation::InconsistentFoundationStack {
+                foundation,
+                expected,
+                found,
+            } => write!(
+                f,
+                "foundation {} should hold [{}] but holds [{}]",
+                foundation,
+                cards(*expected),
+                cards(*found)
+            ),
+            Violation::StaleUnlockedIndex {
+                tableau,
+                expected,
+                found,
+            } => write!(
+                f,
+                "tableau {} has first unlocked index {} instead of {}",
+                tableau, found, expected
+            ),
+        }
+    }
+}
+
+// Every invariant a game state breaks, as found by `Game::validate`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidState(pub Vec<Violation>);
+
+impl fmt::Display for InvalidState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid game state: ")?;
+        let violations = self.0.iter().map(Violation::to_string);
+        write!(f, "{}", violations.collect::<Vec<_>>().join(", "))
+    }
+}
+
+impl std::error::Error for InvalidState {}
+
+// Cards of a suit from the ace up to `top`, as bits of a `foundation_stack`
+fn foundation_mask(suit: u8, top: Card) -> u64 {
+    if top == u8::MAX {
+        0
+    } else {
+        let ace = suit * NUM_CARDS_SUIT;
+        (ace..=top.index()).fold(0, |mask, card| mask | (1 << card))
+    }
+}
+
+impl Game {
+    // Checks that every card is a card of the deck in exactly one place, face up outside the
+    // tableaus, that the foundations agree with
+    // `foundation_stack` and that `first_unlocked_idx` is up to date
+    pub fn validate(&self) -> Result<(), InvalidState> {
+        let mut violations = Vec::new();
+
+        let mut counts = [0u8; NUM_CARDS_DECK as usize];
+        let face_up_cards = self.stock.0.iter().chain(&self.waste.0);
+        for card in face_up_cards.clone() {
+            if !card.is_face_up() && card.index() < NUM_CARDS_DECK {
+                violations.push(Violation::FaceDownOutsideTableau(card.index()));
+            }
+        }
+        let tableau_cards = self.tableaus.iter().flat_map(|tableau| tableau.0.iter());
+        for card in face_up_cards.chain(tableau_cards) {
+            match counts.get_mut(card.index() as usize) {
+                Some(count) => *count += 1,
+                None => violations.push(Violation::UnknownCard(*card)),
+            }
+        }
+
+        for (foundation, top) in self.foundations.iter().enumerate() {
+            let foundation = foundation as u8;
+            if *top != u8::MAX && suit_rank(*top) != foundation {
+                violations.push(Violation::WrongFoundationSuit {
+                    foundation,
+                    card: *top,
+                });
+                continue;
+            }
+            let king = (foundation + 1) * NUM_CARDS_SUIT - 1;
+            let expected = foundation_mask(foundation, *top);
+            let found = self.foundation_stack & foundation_mask(foundation, king);
+            if expected != found {
+                violations.push(Violation::InconsistentFoundationStack {
+                    foundation,
+                    expected,
+                    found,
+                });
+            }
+        }
+
+        for card in 0..NUM_CARDS_DECK {
+            let in_foundation = self.foundation_stack & (1 << card) != 0;
+            match (counts[card as usize], in_foundation) {
+                (0, false) => violations.push(Violation::MissingCard(card)),
+                (1, false) | (0, true) => {}
+                (_, true) => violations.push(Violation::CardInFoundationAndPile(card)),
+                (count, false) => violations.push(Violation::DuplicateCard { card, count }),
+            }
+        }
+
+        for tableau in 0..self.tableaus.len() {
+            let expected = self.compute_first_unlocked_index(tableau);
+            let found = self.first_unlocked_idx[tableau];
+            if expected != found {
+                violations.push(Violation::StaleUnlockedIndex {
+                    tableau: tableau as u8,
+                    expected,
+                    found,
+                });
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidState(violations))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{
+        deal::Deal,
+        moves::{Move, MoveGeneration},
+        rules::{Rules, NUM_TABLEAUS},
+    };
+
+    // The same game with its tableaus reordered, tableau `idx` moving to `order[idx]`
+    fn permute_tableaus(game: &Game, order: &[usize]) -> Game {
+        let mut permuted = game.clone();
+        for (from, to) in order.iter().enumerate() {
+            permuted.tableaus[*to] = game.tableaus[from].clone();
+            permuted.first_unlocked_idx[*to] = game.first_unlocked_idx[from];
+        }
+        permuted
+    }
+
+    fn dealt_game() -> Game {
+        Game::from_deal(&Deal::from_seed(0), Rules::default())
+    }
+
+    #[test]
+    fn cards_past_the_deck_are_reported() {
+        let mut game = dealt_game();
+        game.waste.0.push(NUM_CARDS_DECK + 8);
+        assert_eq!(
+            game.validate(),
+            Err(InvalidState(vec![Violation::UnknownCard(
+                NUM_CARDS_DECK + 8
+            )]))
+        );
+    }
+
+    #[test]
+    fn face_down_cards_in_the_stock_are_reported() {
+        let mut game = dealt_game();
+        let card = game.stock.0[0];
+        game.stock.0[0] = card.face_down();
+        assert_eq!(
+            game.validate(),
+            Err(InvalidState(vec![Violation::FaceDownOutsideTableau(card)]))
+        );
+    }
+
+    proptest! {
+        // Plays a random legal game, picking each move out of the sorted legal moves with the
+        // next choice, and checks the invariants after every move. Shrinking shortens the
+        // game and lowers the choices, so a failure comes down to a short sequence of moves.
+        #[test]
+        fn moves_keep_the_game_valid(
+            seed in any::<u32>(),
+            draw_count in prop_oneof![Just(1u8), Just(3u8)],
+            choices in prop::collection::vec(any::<u8>(), 0..300),
+            order in Just((0..NUM_TABLEAUS).collect::<Vec<_>>()).prop_shuffle(),
+        ) {
+            let mut game = Game::from_deal(&Deal::from_seed(seed), Rules { draw_count, ..Rules::default() });
+            let mut played = Vec::new();
+            for choice in choices {
+                let mut moves = game
+                    .moves(MoveGeneration::Complete)
+                    .into_iter()
+                    .collect::<Vec<Move>>();
+                if moves.is_empty() {
+                    break;
+                }
+                moves.sort();
+                let mv = moves[choice as usize % moves.len()];
+                played.push(mv.to_string());
+                game = match game.handle_move(&mv) {
+                    Ok(game) => game,
+                    Err(err) => {
+                        return Err(TestCaseError::fail(format!("{} after {:?}", err, played)))
+                    }
+                };
+                if let Err(err) = game.validate() {
+                    return Err(TestCaseError::fail(format!(
+                        "{} after {:?}:\n{}",
+                        err, played, game
+                    )));
+                }
+                prop_assert_eq!(
+                    permute_tableaus(&game, &order).compact_state(),
+                    game.compact_state(),
+                    "compact state changed with the tableau order after {:?}",
+                    played
+                );
+            }
+        }
+    }
+}
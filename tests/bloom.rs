@@ -0,0 +1,45 @@
+// A search deduplicating with a Bloom filter may skip states it never visited, so its
+// solutions must still be sound while its unsolvable results don't count as proofs.
+
+mod common;
+
+use solitaire_solvability::{
+    moves::MoveGeneration,
+    results::{Deduplication, Outcome},
+    solver::{Budget, Solver},
+};
+
+fn solver(seed: u32, generation: MoveGeneration) -> Solver {
+    Solver::new(
+        common::config(seed)
+            .with_budget(Budget::unlimited())
+            .with_move_generation(generation),
+    )
+}
+
+#[test]
+fn bloom_solutions_replay() {
+    // 64 KiB is far too small for the ~21,000 states this deal takes, yet the false positives
+    // don't get in the way of the solution
+    for memory_bytes in [1 << 16, 1 << 20] {
+        let result = solver(3035812914, MoveGeneration::Pruned)
+            .with_bloom_filter(memory_bytes)
+            .solve();
+        assert_eq!(result.outcome, Outcome::Solved);
+        assert!(result.replay().unwrap().is_game_won());
+        assert_eq!(result.deduplication, Deduplication::Bloom { memory_bytes });
+    }
+}
+
+#[test]
+fn bloom_unsolvable_is_not_a_proof() {
+    let exact = solver(282, MoveGeneration::Complete).solve();
+    assert_eq!(exact.outcome, Outcome::Unsolvable);
+    assert!(exact.is_proof());
+
+    let bloom = solver(282, MoveGeneration::Complete)
+        .with_bloom_filter(1 << 20)
+        .solve();
+    assert_eq!(bloom.outcome, Outcome::Unsolvable);
+    assert!(!bloom.is_proof());
+}
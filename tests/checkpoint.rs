@@ -0,0 +1,50 @@
+// A search saved to a checkpoint and resumed, possibly over several sessions, must end the
+// same way as one that ran in one go.
+
+mod common;
+
+use solitaire_solvability::{
+    results::Outcome,
+    solver::{Budget, Solver},
+};
+use std::{fs, time::Duration};
+
+// Takes about 21,000 states to solve
+const SEED: u32 = 3035812914;
+
+#[test]
+fn resumed_search_matches_uninterrupted_search() {
+    let path = std::env::temp_dir().join(format!("checkpoint-{}.bin", std::process::id()));
+    let uninterrupted = Solver::new(common::config(SEED).with_budget(Budget::unlimited())).solve();
+    assert_eq!(uninterrupted.outcome, Outcome::Solved);
+
+    // Checkpoints are only written on a timeout here, the interval is never reached
+    let first = Solver::new(common::config(SEED).with_budget(Budget::nodes(5_000)))
+        .with_checkpoint(&path, Duration::MAX)
+        .solve();
+    assert_eq!(first.outcome, Outcome::Timeout);
+    let second = Solver::from_checkpoint(&path, Budget::nodes(12_000), None)
+        .unwrap()
+        .with_checkpoint(&path, Duration::MAX)
+        .solve();
+    assert_eq!(second.outcome, Outcome::Timeout);
+    assert_eq!(second.stats.expanded_nodes, 12_000);
+    let last = Solver::from_checkpoint(&path, Budget::unlimited(), None)
+        .unwrap()
+        .solve();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(last.outcome, Outcome::Solved);
+    assert_eq!(last.solution, uninterrupted.solution);
+    assert_eq!(
+        last.stats.expanded_nodes,
+        uninterrupted.stats.expanded_nodes
+    );
+    assert!(last.replay().unwrap().is_game_won());
+}
+
+#[test]
+fn missing_checkpoint_is_an_error() {
+    let path = std::env::temp_dir().join("no-such-checkpoint.bin");
+    assert!(Solver::from_checkpoint(path, Budget::default(), None).is_err());
+}
@@ -0,0 +1,46 @@
+// Helpers shared by the integration tests. Each test crate uses only some of them.
+#![allow(dead_code)]
+
+use std::{fmt::Debug, fmt::Display, str::FromStr};
+
+use solitaire_solvability::{deal::Deal, rules::Rules, solver::SearchConfig};
+
+// The settings of a search of the deal of `seed` under the default rules, to build on
+pub fn config(seed: u32) -> SearchConfig {
+    SearchConfig::from_deal(Deal::from_seed(seed), Rules::default())
+}
+
+// A small LCG picking moves of random walks, so that a walk is the same on every run
+pub struct Walk {
+    state: u64,
+}
+
+impl Walk {
+    pub fn new(seed: u32) -> Self {
+        Self { state: seed as u64 }
+    }
+
+    // An index below `len`, which must not be 0
+    pub fn pick(&mut self, len: usize) -> usize {
+        self.state = self
+            .state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.state >> 33) as usize % len
+    }
+}
+
+// Checks that every one of `values` parses back from its name, and that none of `rejected`
+// parses
+pub fn check_parses_from_names<T>(values: impl IntoIterator<Item = T>, rejected: &[&str])
+where
+    T: FromStr + Display + PartialEq + Debug,
+    T::Err: Debug,
+{
+    for value in values {
+        assert_eq!(value.to_string().parse::<T>().unwrap(), value);
+    }
+    for name in rejected {
+        assert!(name.parse::<T>().is_err(), "{} parses", name);
+    }
+}
@@ -0,0 +1,45 @@
+// Deadlock pruning only drops states that can't lead to a win, so it must never change the
+// outcome of a search, only how many states it takes to get there.
+
+mod common;
+
+use solitaire_solvability::{
+    moves::MoveGeneration,
+    results::Outcome,
+    solver::{Budget, SearchConfig, Solver},
+};
+
+fn config(seed: u32, prune_deadlocks: bool) -> SearchConfig {
+    common::config(seed)
+        .with_budget(Budget::nodes(100_000))
+        .with_deadlock_pruning(prune_deadlocks)
+}
+
+#[test]
+fn deadlocked_deal_is_proven_unsolvable_at_once() {
+    // The search runs out of budget on 14 without pruning
+    let result =
+        Solver::new(config(14, true).with_move_generation(MoveGeneration::Complete)).solve();
+    assert_eq!(result.outcome, Outcome::Unsolvable);
+    assert_eq!(result.stats.expanded_nodes, 1);
+    assert_eq!(result.stats.deadlocks, 1);
+    assert!(result.is_proof());
+}
+
+#[test]
+fn pruning_keeps_solutions_and_saves_states() {
+    for seed in [0, 1, 121, 3035812914] {
+        let pruned = Solver::new(config(seed, true)).solve();
+        let unpruned = Solver::new(config(seed, false)).solve();
+        assert_eq!(pruned.outcome, Outcome::Solved, "seed {}", seed);
+        assert!(
+            pruned.stats.expanded_nodes <= unpruned.stats.expanded_nodes,
+            "seed {}",
+            seed
+        );
+        if unpruned.outcome == Outcome::Solved {
+            assert_eq!(pruned.solution, unpruned.solution, "seed {}", seed);
+        }
+        assert_eq!(unpruned.stats.deadlocks, 0);
+    }
+}
@@ -0,0 +1,122 @@
+// Differential tests between the pruned and complete move generators. The pruned generator
+// skips partial stack moves that don't uncover a foundation card, which could cut off every
+// winning line of a deal. These flag any deal it calls unsolvable that the complete one solves.
+//
+// The default corpus is small enough for a debug build. Run the wide one with
+//   cargo test --release --test differential -- --ignored
+
+mod common;
+
+use common::Walk;
+use rayon::prelude::*;
+use solitaire_solvability::{
+    deal::Deal,
+    moves::{Move, MoveGeneration},
+    results::Outcome,
+    rules::Rules,
+    solver::{Budget, Solver},
+    Game,
+};
+
+// Seeds of each corpus both generators resolve within the budget, so that a change that makes
+// them time out more can't pass by comparing fewer deals
+const MIN_COMPARED: usize = 15;
+const MIN_COMPARED_WIDE: usize = 324;
+
+fn solve(seed: u32, generation: MoveGeneration, max_nodes: u64) -> Outcome {
+    let config = common::config(seed)
+        .with_budget(Budget::nodes(max_nodes))
+        .with_move_generation(generation);
+    Solver::new(config).solve().outcome
+}
+
+// Seeds the pruned generator proves unsolvable within the budget but the complete one solves,
+// and the number of seeds both generators resolved. A seed either of them times out on
+// compares nothing.
+fn pruned_misses(
+    seeds: impl IntoParallelIterator<Item = u32>,
+    max_nodes: u64,
+) -> (Vec<u32>, usize) {
+    let outcomes = seeds
+        .into_par_iter()
+        .map(|seed| {
+            let pruned = solve(seed, MoveGeneration::Pruned, max_nodes);
+            let complete = solve(seed, MoveGeneration::Complete, max_nodes);
+            (seed, pruned, complete)
+        })
+        .collect::<Vec<_>>();
+    let compared = outcomes
+        .iter()
+        .filter(|(_, pruned, complete)| {
+            *pruned != Outcome::Timeout && *complete != Outcome::Timeout
+        })
+        .count();
+    let mut misses = outcomes
+        .into_iter()
+        .filter(|(_, pruned, complete)| {
+            *pruned == Outcome::Unsolvable && *complete == Outcome::Solved
+        })
+        .map(|(seed, _, _)| seed)
+        .collect::<Vec<_>>();
+    misses.sort();
+    (misses, compared)
+}
+
+#[test]
+fn pruned_generator_keeps_winning_lines() {
+    // The pruned generator proves 282 unsolvable within a hundred states
+    let corpus = (0..20).chain([282]).collect::<Vec<_>>();
+    let (misses, compared) = pruned_misses(corpus, 10_000);
+    assert_eq!(misses, Vec::<u32>::new());
+    assert!(compared >= MIN_COMPARED, "only {} seeds compared", compared);
+}
+
+#[test]
+#[ignore]
+fn pruned_generator_keeps_winning_lines_wide() {
+    let (misses, compared) = pruned_misses(0..500, 200_000);
+    assert_eq!(misses, Vec::<u32>::new());
+    assert!(
+        compared >= MIN_COMPARED_WIDE,
+        "only {} seeds compared",
+        compared
+    );
+}
+
+// Walks a deal picking moves from the complete generator, checking at every state that the
+// pruned moves are among the complete ones and that every complete move can be played
+fn check_generators_along_walk(seed: u32, steps: usize) {
+    let mut game = Game::from_deal(&Deal::from_seed(seed), Rules::default());
+    let mut walk = Walk::new(seed);
+    for _ in 0..steps {
+        let pruned = game.moves(MoveGeneration::Pruned);
+        let complete = game.moves(MoveGeneration::Complete);
+        assert!(
+            pruned.is_subset(&complete),
+            "seed {}: pruned moves {:?} are not all complete moves {:?}",
+            seed,
+            pruned,
+            complete
+        );
+        let mut complete = complete.into_iter().collect::<Vec<Move>>();
+        if complete.is_empty() {
+            return;
+        }
+        complete.sort_by_key(Move::to_string);
+        let next_games = complete
+            .iter()
+            .map(|mv| {
+                game.handle_move(mv)
+                    .unwrap_or_else(|err| panic!("seed {}: {} failed: {}", seed, mv, err))
+            })
+            .collect::<Vec<_>>();
+        game = next_games[walk.pick(next_games.len())].clone();
+    }
+}
+
+#[test]
+fn pruned_moves_are_complete_moves() {
+    (0..20).into_par_iter().for_each(|seed| {
+        check_generators_along_walk(seed, 200);
+    });
+}
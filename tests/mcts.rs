@@ -0,0 +1,80 @@
+// The MCTS player proves nothing, but the games it reports must be legal, end where it says
+// and never come back to a state, and the same seed must play the same game.
+
+mod common;
+
+use std::{collections::HashSet, time::Duration};
+
+use solitaire_solvability::{
+    deal::Deal,
+    mcts::{MctsPlayer, PlayResult, Rollout},
+    rules::Rules,
+};
+
+const ROLLOUTS: [Rollout; 2] = [Rollout::Random, Rollout::Heuristic];
+
+fn play(seed: u32, rollout: Rollout) -> PlayResult {
+    MctsPlayer::from_deal(Deal::from_seed(seed), Rules::default())
+        .with_iterations(5)
+        .with_rollout(rollout)
+        .play()
+        .unwrap()
+}
+
+#[test]
+fn played_games_replay_to_their_end() {
+    // 1 and 3 are won with either rollout at this budget, 14 is deadlocked from the start
+    for rollout in ROLLOUTS {
+        for (seed, won) in [(1, true), (3, true), (14, false)] {
+            let result = play(seed, rollout);
+            assert_eq!(result.won, won, "seed {} with {}", seed, rollout);
+            let mut game = result.game();
+            let mut states = HashSet::from([game.compact_state()]);
+            for mv in &result.moves {
+                game = game.handle_move(mv).unwrap();
+                assert!(
+                    states.insert(game.compact_state()),
+                    "seed {} with {}: {} comes back to a state",
+                    seed,
+                    rollout,
+                    mv
+                );
+            }
+            assert_eq!(game.is_game_won(), won, "seed {} with {}", seed, rollout);
+            assert_eq!(result.replay().unwrap(), game);
+            if won {
+                assert_eq!(result.foundation_cards, 52);
+            }
+        }
+    }
+}
+
+#[test]
+fn same_seed_plays_the_same_game() {
+    for rollout in ROLLOUTS {
+        assert_eq!(
+            play(3, rollout).moves,
+            play(3, rollout).moves,
+            "{}",
+            rollout
+        );
+    }
+}
+
+#[test]
+fn zero_time_per_move_still_plays() {
+    // A single iteration per move, so the game is played rather than reported lost at once
+    let result = MctsPlayer::from_deal(Deal::from_seed(1), Rules::default())
+        .with_time_per_move(Duration::ZERO)
+        .play()
+        .unwrap();
+    assert!(!result.moves.is_empty());
+    // Moves without an alternative are played without searching
+    assert!(result.iterations > 0 && result.iterations <= result.moves.len() as u64);
+    assert_eq!(result.replay().unwrap().is_game_won(), result.won);
+}
+
+#[test]
+fn rollouts_parse_from_their_names() {
+    common::check_parses_from_names(ROLLOUTS, &["greedy"]);
+}
@@ -0,0 +1,65 @@
+// The parallel solver must reach the same outcome as the sequential one, whatever the
+// interleaving of its workers, and return solutions that replay.
+
+mod common;
+
+use solitaire_solvability::{
+    parallel::ParallelSolver,
+    results::Outcome,
+    solver::{Budget, CancellationToken, Solver},
+};
+
+// Searches are unlimited unless the test is about the budget, so that the outcome doesn't
+// depend on how many states the workers happen to expand before one of them wins. The pruned
+// search of every deal below is finite, so the search always ends, with a solution if the
+// deal has one.
+
+#[test]
+fn parallel_solutions_replay() {
+    for seed in [0, 1, 1055199751, 3044533588] {
+        for threads in [1, 2, 4] {
+            let result = ParallelSolver::new(common::config(seed).with_budget(Budget::unlimited()))
+                .with_threads(threads)
+                .solve();
+            assert_eq!(result.outcome, Outcome::Solved, "seed {}", seed);
+            assert!(result.replay().unwrap().is_game_won(), "seed {}", seed);
+        }
+    }
+}
+
+#[test]
+fn parallel_search_exhausts_unsolvable_deals() {
+    // The pruned search space of 282 has 70 states, every worker must agree it holds no win
+    let sequential = Solver::new(common::config(282).with_budget(Budget::unlimited())).solve();
+    for threads in [1, 2, 4, 8] {
+        let parallel = ParallelSolver::new(common::config(282).with_budget(Budget::unlimited()))
+            .with_threads(threads)
+            .solve();
+        assert_eq!(parallel.outcome, Outcome::Unsolvable);
+        assert_eq!(
+            parallel.stats.peak_visited_size,
+            sequential.stats.peak_visited_size
+        );
+    }
+}
+
+#[test]
+fn parallel_search_stops_at_the_node_budget() {
+    let result = ParallelSolver::new(common::config(422).with_budget(Budget::nodes(1_000)))
+        .with_threads(4)
+        .solve();
+    assert_eq!(result.outcome, Outcome::Timeout);
+    assert!(result.solution.is_empty());
+}
+
+#[test]
+fn cancelled_parallel_search_stops_with_cancelled() {
+    let token = CancellationToken::new();
+    token.cancel();
+    let config = common::config(422)
+        .with_budget(Budget::unlimited())
+        .with_cancellation(token);
+    let result = ParallelSolver::new(config).with_threads(4).solve();
+    assert_eq!(result.outcome, Outcome::Cancelled);
+    assert!(result.solution.is_empty());
+}
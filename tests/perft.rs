@@ -0,0 +1,80 @@
+// Golden perft counts: the move sequences of every length from a few deals, and the distinct
+// positions they reach. A change to move generation, canonicalization or the rules changes
+// these, and should come with an explanation of why the new counts are right.
+//
+// Regenerate a row with `solitaire_solvability perft --seed <seed> --depth 12 --moves <moves>`.
+
+use solitaire_solvability::{
+    deal::Deal,
+    moves::MoveGeneration,
+    perft::{perft, PerftCounts},
+    rules::Rules,
+    Game,
+};
+
+// (paths, positions) at depths 0 to 12
+type Golden = [(u64, usize); 13];
+
+#[rustfmt::skip]
+const GOLDEN: [(u32, MoveGeneration, Golden); 8] = [
+    (0, MoveGeneration::Pruned, [
+        (1, 1), (1, 1), (3, 3), (4, 4), (5, 4), (6, 4), (8, 5), (13, 9), (29, 17), (70, 32),
+        (187, 56), (509, 93), (1394, 150),
+    ]),
+    (0, MoveGeneration::Complete, [
+        (1, 1), (1, 1), (3, 3), (4, 4), (5, 4), (6, 4), (8, 5), (13, 9), (29, 17), (70, 32),
+        (188, 57), (517, 97), (1458, 162),
+    ]),
+    (1, MoveGeneration::Pruned, [
+        (1, 1), (2, 2), (4, 3), (7, 3), (12, 4), (24, 7), (63, 13), (170, 23), (542, 42),
+        (1821, 70), (6354, 108), (22631, 165), (83585, 254),
+    ]),
+    (1, MoveGeneration::Complete, [
+        (1, 1), (2, 2), (4, 3), (7, 3), (12, 4), (24, 7), (63, 13), (170, 23), (542, 42),
+        (1821, 70), (6375, 109), (22862, 168), (85462, 263),
+    ]),
+    (2, MoveGeneration::Pruned, [
+        (1, 1), (2, 2), (5, 4), (13, 7), (33, 10), (80, 13), (171, 16), (331, 21), (535, 29),
+        (985, 40), (2008, 53), (4095, 68), (7659, 86),
+    ]),
+    (2, MoveGeneration::Complete, [
+        (1, 1), (2, 2), (5, 4), (13, 7), (34, 11), (86, 15), (208, 20), (486, 28), (1118, 40),
+        (2580, 56), (6055, 77), (14554, 105), (35898, 143),
+    ]),
+    (3, MoveGeneration::Pruned, [
+        (1, 1), (2, 2), (5, 4), (12, 6), (15, 6), (19, 7), (24, 9), (27, 9), (30, 9), (42, 11),
+        (76, 17), (122, 22), (168, 24),
+    ]),
+    (3, MoveGeneration::Complete, [
+        (1, 1), (2, 2), (5, 4), (14, 8), (29, 12), (63, 17), (138, 23), (309, 29), (694, 35),
+        (1556, 43), (3450, 55), (7692, 70), (17468, 86),
+    ]),
+];
+
+#[test]
+fn perft_matches_golden_counts() {
+    for (seed, generation, golden) in GOLDEN {
+        let game = Game::from_deal(&Deal::from_seed(seed), Rules::default());
+        let counts = perft(&game, golden.len() as u32 - 1, generation).unwrap();
+        let expected = golden
+            .iter()
+            .enumerate()
+            .map(|(depth, &(paths, positions))| PerftCounts {
+                depth: depth as u32,
+                paths,
+                positions,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(counts, expected, "seed {} with {} moves", seed, generation);
+    }
+}
+
+#[test]
+fn perft_is_stable_across_runs() {
+    // Moves come out of a hash set in a different order every run, the counts must not care
+    let game = Game::from_deal(&Deal::from_seed(1), Rules::default());
+    let first = perft(&game, 8, MoveGeneration::Complete).unwrap();
+    for _ in 0..3 {
+        assert_eq!(perft(&game, 8, MoveGeneration::Complete).unwrap(), first);
+    }
+}
@@ -0,0 +1,188 @@
+// Regression corpus of the deals the first versions of the solver recorded as solvable, loaded
+// from the files they wrote at the root of the repository:
+//   solvable_games.json          the seed and move list of each solved deal, with moves in
+//                                the derived serde format of the time
+//   compact_solvable_games.txt   the stock of each of those deals, in the same order
+//   compact_solvable_games.json  the seeds of more solved deals, without their moves
+//
+// The recorded move lists are replayed, and every deal is solved again and its new solution
+// checked. The hardest deals only run with
+//   cargo test --release --test regression -- --ignored
+
+use rayon::prelude::*;
+use serde::Deserialize;
+use solitaire_solvability::{
+    card::IsCard,
+    deal::Deal,
+    moves::{CardPosition, Move, MoveGeneration},
+    results::Outcome,
+    rules::Rules,
+    solver::{Budget, SearchConfig, Solver},
+    Game,
+};
+
+// States the quick test gives each deal
+const QUICK_MAX_NODES: u64 = 20_000;
+
+// Deals that take more than `QUICK_MAX_NODES` states to solve, left to the ignored test
+const HARD_SEEDS: [u32; 20] = [
+    3367802734, 919206715, 2955279615, 3876665739, 3282031814, 3654388914, 2453146303, 1268242272,
+    1989802602, 3797994024, 4024006225, 2385713334, 1759113897, 4035168353, 1497363408, 965792215,
+    681733007, 490447741, 2478293856, 3035812914,
+];
+
+// The only recorded move list that wins its deal. The others were recorded by a solver that
+// lost track of its path when it backtracked, and most of them fail within a few moves.
+const WINNING_RECORDED_SOLUTIONS: [u32; 1] = [2502868284];
+
+// A card position as the derived serde implementation wrote it, e.g. `{"Tableau": [5, 6]}`
+#[derive(Deserialize)]
+enum RecordedPosition {
+    Stock,
+    Waste,
+    Foundation(u8),
+    Tableau((u8, u8)),
+}
+
+impl From<RecordedPosition> for CardPosition {
+    fn from(position: RecordedPosition) -> Self {
+        match position {
+            RecordedPosition::Stock => CardPosition::Stock,
+            RecordedPosition::Waste => CardPosition::Waste,
+            RecordedPosition::Foundation(idx) => CardPosition::Foundation(idx),
+            RecordedPosition::Tableau(position) => CardPosition::Tableau(position),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RecordedMove {
+    from: RecordedPosition,
+    to: RecordedPosition,
+}
+
+#[derive(Deserialize)]
+struct RecordedGame {
+    random_seed: u32,
+    moves_made: Vec<RecordedMove>,
+}
+
+#[derive(Deserialize)]
+struct SolvableGames {
+    solvers: Vec<RecordedGame>,
+}
+
+#[derive(Deserialize)]
+struct CompactSolvableGames {
+    seeds: Vec<u32>,
+}
+
+// Seeds and recorded moves of the deals of `solvable_games.json`
+fn recorded_games() -> Vec<(u32, Vec<Move>)> {
+    let games: SolvableGames = serde_json::from_str(include_str!("../solvable_games.json"))
+        .expect("could not parse solvable_games.json");
+    games
+        .solvers
+        .into_iter()
+        .map(|game| {
+            let moves = game.moves_made.into_iter().map(|mv| {
+                Move {
+                    from: mv.from.into(),
+                    to: mv.to.into(),
+                }
+                .normalized()
+            });
+            (game.random_seed, moves.collect())
+        })
+        .collect()
+}
+
+// Every seed of the corpus, from both files
+fn corpus_seeds() -> Vec<u32> {
+    let compact: CompactSolvableGames =
+        serde_json::from_str(include_str!("../compact_solvable_games.json"))
+            .expect("could not parse compact_solvable_games.json");
+    let recorded = recorded_games().into_iter().map(|(seed, _)| seed);
+    recorded.chain(compact.seeds).collect()
+}
+
+// Plays `solution` on the deal, returning the state it ends in, or the index of the first
+// move that is not legal
+fn replay(seed: u32, solution: &[Move]) -> Result<Game, usize> {
+    let mut game = Game::from_deal(&Deal::from_seed(seed), Rules::default());
+    for (idx, mv) in solution.iter().enumerate() {
+        if !game.moves(MoveGeneration::Complete).contains(mv) {
+            return Err(idx);
+        }
+        game = game
+            .handle_move(mv)
+            .unwrap_or_else(|err| panic!("seed {}: move {} ({}) failed: {}", seed, idx, mv, err));
+    }
+    Ok(game)
+}
+
+fn check_solves_again(seed: u32, max_nodes: u64) {
+    let config = SearchConfig::from_deal(Deal::from_seed(seed), Rules::default())
+        .with_budget(Budget::nodes(max_nodes));
+    let result = Solver::new(config).solve();
+    assert_eq!(
+        result.outcome,
+        Outcome::Solved,
+        "seed {}: not solved within {} states",
+        seed,
+        max_nodes
+    );
+    match replay(seed, &result.solution) {
+        Ok(game) => assert!(game.is_game_won(), "seed {}: solution doesn't win", seed),
+        Err(idx) => panic!("seed {}: move {} of the solution is not legal", seed, idx),
+    }
+}
+
+#[test]
+fn corpus_deals_are_distinct() {
+    let mut seeds = corpus_seeds();
+    let len = seeds.len();
+    seeds.sort();
+    seeds.dedup();
+    assert_eq!(seeds.len(), len);
+}
+
+#[test]
+fn recorded_stocks_match_their_deals() {
+    let stocks: Vec<Vec<u8>> = serde_json::from_str(include_str!("../compact_solvable_games.txt"))
+        .expect("could not parse compact_solvable_games.txt");
+    let games = recorded_games();
+    assert_eq!(stocks.len(), games.len());
+    for ((seed, _), stock) in games.iter().zip(stocks) {
+        let game = Game::from_deal(&Deal::from_seed(*seed), Rules::default());
+        let dealt = game.stock.0.iter().map(|card| card.index());
+        assert_eq!(dealt.collect::<Vec<_>>(), stock, "seed {}", seed);
+    }
+}
+
+#[test]
+fn recorded_solutions_replay() {
+    let mut winning = recorded_games()
+        .into_iter()
+        .filter(|(seed, moves)| replay(*seed, moves).is_ok_and(|game| game.is_game_won()))
+        .map(|(seed, _)| seed)
+        .collect::<Vec<_>>();
+    winning.sort();
+    assert_eq!(winning, WINNING_RECORDED_SOLUTIONS);
+}
+
+#[test]
+fn corpus_deals_solve_again() {
+    corpus_seeds()
+        .into_par_iter()
+        .filter(|seed| !HARD_SEEDS.contains(seed))
+        .for_each(|seed| check_solves_again(seed, QUICK_MAX_NODES));
+}
+
+#[test]
+#[ignore]
+fn hard_corpus_deals_solve_again() {
+    HARD_SEEDS
+        .into_par_iter()
+        .for_each(|seed| check_solves_again(seed, 20_000_000));
+}
@@ -0,0 +1,142 @@
+// The relaxed check proves a deal unsolvable by exhausting a search under rules that allow
+// more moves. That only holds if every move of the real game stays legal in the relaxed one,
+// and the relaxed search must never turn a winnable deal into an unsolvable one.
+
+mod common;
+
+use common::Walk;
+use solitaire_solvability::{
+    card::*,
+    deal::Deal,
+    moves::{Move, MoveGeneration},
+    results::Outcome,
+    rules::{Relaxation, Rules},
+    solver::{Budget, SearchConfig, Solver},
+    Game,
+};
+
+const RELAXATIONS: [Relaxation; 3] = [
+    Relaxation {
+        ignore_colors: true,
+        any_card_on_empty_tableau: false,
+    },
+    Relaxation {
+        ignore_colors: false,
+        any_card_on_empty_tableau: true,
+    },
+    Relaxation {
+        ignore_colors: true,
+        any_card_on_empty_tableau: true,
+    },
+];
+
+fn solver(deal: Deal, generation: MoveGeneration) -> Solver {
+    Solver::new(
+        SearchConfig::from_deal(deal, Rules::default())
+            .with_budget(Budget::nodes(100_000))
+            .with_move_generation(generation),
+    )
+}
+
+// Seed 0 with the 2 of clubs and every 6 dealt under the 5 of clubs, which tops tableau 6.
+// The 5 can go neither to a foundation nor onto a 6, whatever the colours, so the deal can't
+// be won unless it can go to an empty tableau.
+fn hopeless_deal() -> Deal {
+    const TABLEAU_6: [usize; 7] = [45, 39, 34, 30, 27, 25, 24];
+    let placed = [
+        CLUBS_ACE + 1,
+        CLUBS_ACE + 5,
+        SPADES_ACE + 5,
+        HEARTS_ACE + 5,
+        DIAMONDS_ACE + 5,
+        CLUBS_ACE + 4,
+    ];
+    let mut deal = Deal::from_seed(0);
+    for (position, card) in TABLEAU_6[1..].iter().zip(placed) {
+        let from = deal.cards.iter().position(|dealt| *dealt == card).unwrap();
+        deal.cards.swap(*position, from);
+    }
+    deal
+}
+
+#[test]
+fn real_moves_stay_legal_under_relaxed_rules() {
+    for relaxation in RELAXATIONS {
+        for seed in 0..10 {
+            let deal = Deal::from_seed(seed);
+            let mut game = Game::from_deal(&deal, Rules::default());
+            let mut relaxed = Game::from_deal(
+                &deal,
+                Rules {
+                    relaxation,
+                    ..Rules::default()
+                },
+            );
+            let mut walk = Walk::new(seed);
+            for _ in 0..200 {
+                let moves = game.moves(MoveGeneration::Complete);
+                let relaxed_moves = relaxed.moves(MoveGeneration::Complete);
+                assert!(
+                    moves.is_subset(&relaxed_moves),
+                    "seed {} under {}: moves {:?} are not all relaxed moves {:?}",
+                    seed,
+                    relaxation,
+                    moves,
+                    relaxed_moves
+                );
+                let mut moves = moves.into_iter().collect::<Vec<Move>>();
+                if moves.is_empty() {
+                    break;
+                }
+                moves.sort();
+                let mv = moves[walk.pick(moves.len())];
+                game = game.handle_move(&mv).unwrap();
+                relaxed = relaxed.handle_move(&mv).unwrap();
+                assert_eq!(game.compact_state(), relaxed.compact_state());
+            }
+        }
+    }
+}
+
+#[test]
+fn hopeless_relaxed_deal_is_proven_unsolvable() {
+    let colors = RELAXATIONS[0];
+    let result = solver(hopeless_deal(), MoveGeneration::Complete)
+        .with_relaxed_check(colors, 1000)
+        .solve();
+    assert_eq!(result.outcome, Outcome::Unsolvable);
+    assert_eq!(result.relaxation, Some(colors));
+    assert_eq!(result.stats.expanded_nodes, 1);
+    assert!(result.is_proof());
+
+    // Lifting the kings-only rule frees the 5 of clubs, so that relaxed search proves
+    // nothing and the full search decides
+    let empty_tableau = RELAXATIONS[1];
+    let result = solver(hopeless_deal(), MoveGeneration::Complete)
+        .with_relaxed_check(empty_tableau, 1000)
+        .solve();
+    assert_eq!(result.outcome, Outcome::Unsolvable);
+    assert_eq!(result.relaxation, None);
+    assert!(result.is_proof());
+}
+
+#[test]
+fn relaxed_check_keeps_real_solutions() {
+    for relaxation in RELAXATIONS {
+        for seed in [0, 1, 3035812914] {
+            let result = solver(Deal::from_seed(seed), MoveGeneration::Pruned)
+                .with_relaxed_check(relaxation, 1000)
+                .solve();
+            assert_eq!(result.outcome, Outcome::Solved, "seed {}", seed);
+            assert_eq!(result.relaxation, None);
+            assert_eq!(result.rules, Rules::default());
+            assert!(result.replay().unwrap().is_game_won(), "seed {}", seed);
+        }
+    }
+}
+
+#[test]
+fn relaxations_parse_from_their_names() {
+    common::check_parses_from_names(RELAXATIONS, &["kings"]);
+    assert_eq!("colors".parse(), Ok(RELAXATIONS[0]));
+}
@@ -0,0 +1,97 @@
+// Every search strategy shares move generation, deduplication and budgets, so the exhaustive
+// ones must visit the same states of an unsolvable deal, and all of them must return
+// solutions that replay.
+
+mod common;
+
+use solitaire_solvability::{
+    deal::Deal,
+    moves::{CardPosition, Move, MoveGeneration},
+    results::Outcome,
+    rules::Rules,
+    solver::{Budget, Solver},
+    strategy::{SearchNode, Strategy},
+    Game,
+};
+
+const EXHAUSTIVE: [Strategy; 4] = [
+    Strategy::Dfs,
+    Strategy::Bfs,
+    Strategy::BestFirst,
+    Strategy::IterativeDeepening,
+];
+
+fn solver(seed: u32, strategy: Strategy, generation: MoveGeneration) -> Solver {
+    let config = common::config(seed)
+        .with_budget(Budget::nodes(200_000))
+        .with_move_generation(generation);
+    Solver::new(config).with_strategy(strategy)
+}
+
+#[test]
+fn exhaustive_strategies_visit_the_same_states() {
+    // Every legal move of 282 leads to 5,052 states, none of them won
+    for strategy in EXHAUSTIVE {
+        let result = solver(282, strategy, MoveGeneration::Complete).solve();
+        assert_eq!(result.outcome, Outcome::Unsolvable, "{}", strategy);
+        assert_eq!(result.stats.peak_visited_size, 5_052, "{}", strategy);
+        assert!(result.is_proof(), "{}", strategy);
+    }
+}
+
+#[test]
+fn beam_search_is_no_proof() {
+    let result = solver(282, Strategy::Beam { width: 10 }, MoveGeneration::Complete).solve();
+    assert_eq!(result.outcome, Outcome::Unsolvable);
+    assert!(!result.is_proof());
+}
+
+#[test]
+fn strategy_solutions_replay() {
+    // Breadth-first and iterative deepening go too wide to reach a win from the deal within
+    // the budget, see the solver tests for their solutions from the middle of a game
+    for strategy in [
+        Strategy::Dfs,
+        Strategy::BestFirst,
+        Strategy::Beam { width: 1000 },
+    ] {
+        for seed in [0, 3035812914] {
+            let result = solver(seed, strategy, MoveGeneration::Pruned).solve();
+            assert_eq!(result.outcome, Outcome::Solved, "{} on {}", strategy, seed);
+            assert_eq!(result.strategy, strategy);
+            assert!(
+                result.replay().unwrap().is_game_won(),
+                "{} on {}",
+                strategy,
+                seed
+            );
+        }
+    }
+}
+
+#[test]
+fn strategies_parse_from_their_names() {
+    common::check_parses_from_names(
+        EXHAUSTIVE.into_iter().chain([Strategy::Beam { width: 50 }]),
+        &["beam:0", "astar"],
+    );
+    assert_eq!(
+        "beam".parse::<Strategy>(),
+        Ok(Strategy::Beam { width: 1000 })
+    );
+}
+
+#[test]
+fn long_branches_drop_without_overflowing_the_stack() {
+    let game = Game::from_deal(&Deal::from_seed(0), Rules::default());
+    let draw = Move {
+        from: CardPosition::Stock,
+        to: CardPosition::Waste,
+    };
+    let mut node = SearchNode::root(game.clone());
+    for _ in 0..1_000_000 {
+        node = node.child(draw, game.clone());
+    }
+    assert_eq!(node.depth, 1_000_000);
+    drop(node);
+}